@@ -0,0 +1,499 @@
+use crate::battle::{Action, Battle, Side, MIN_DAMAGE_ROLL, STRUGGLE_RECOIL_DIVISOR};
+use crate::container::Container;
+use crate::moves::{EffectType, Move, MoveCategory, StatStages, TypeEffectiveness, Weather};
+use rand::RngCore;
+use std::{fmt, str::FromStr};
+
+/// Turns `LookaheadAi` plays out ahead of the current one.
+pub const LOOKAHEAD_DEPTH: u32 = 2;
+
+/// What an AI can see of the battle besides the two Pokémon.
+pub struct BattleView<'a> {
+    pub chart: &'a TypeEffectiveness,
+    pub weather: Option<Weather>,
+}
+
+/// Decides what a Pokémon does each turn of a battle.
+pub trait BattleAi {
+    /// The action `pokemon` takes against `opponent` this turn, or `None` to
+    /// forfeit the battle. Random choices must come from `rng` so that a seeded
+    /// battle plays out the same way again.
+    fn choose_action(
+        &mut self,
+        pokemon: &Container,
+        opponent: &Container,
+        view: &BattleView,
+        rng: &mut dyn RngCore,
+    ) -> Option<Action>;
+}
+
+/// Any move with PP left, at random.
+pub struct RandomAi;
+
+impl BattleAi for RandomAi {
+    fn choose_action(
+        &mut self,
+        pokemon: &Container,
+        _opponent: &Container,
+        _view: &BattleView,
+        mut rng: &mut dyn RngCore,
+    ) -> Option<Action> {
+        Some(Battle::choose_action(pokemon, &mut rng))
+    }
+}
+
+/// The move with the highest expected damage this turn.
+pub struct GreedyAi;
+
+impl BattleAi for GreedyAi {
+    fn choose_action(
+        &mut self,
+        pokemon: &Container,
+        opponent: &Container,
+        view: &BattleView,
+        _rng: &mut dyn RngCore,
+    ) -> Option<Action> {
+        Some(strongest_attack(pokemon, opponent, view))
+    }
+}
+
+/// While it has more of its HP left than the opponent, uses status moves that
+/// would still do something (a status the opponent can get, a stat that isn't
+/// at its limit, weather that isn't up yet); otherwise attacks like `GreedyAi`.
+pub struct DefensiveAi;
+
+impl BattleAi for DefensiveAi {
+    fn choose_action(
+        &mut self,
+        pokemon: &Container,
+        opponent: &Container,
+        view: &BattleView,
+        _rng: &mut dyn RngCore,
+    ) -> Option<Action> {
+        if hp_fraction(pokemon) > hp_fraction(opponent) {
+            let useful = usable_moves(pokemon).find(|(_, battle_move)| {
+                battle_move.category == MoveCategory::Status
+                    && has_useful_effect(battle_move, pokemon, opponent, view)
+            });
+            if let Some((index, _)) = useful {
+                return Some(Action::Move(index));
+            }
+        }
+        Some(strongest_attack(pokemon, opponent, view))
+    }
+}
+
+/// Plays every pair of moves `depth` turns ahead with expected damage and picks
+/// the move whose worst outcome leaves it best off in HP against the opponent.
+pub struct LookaheadAi {
+    pub depth: u32,
+}
+
+impl BattleAi for LookaheadAi {
+    fn choose_action(
+        &mut self,
+        pokemon: &Container,
+        opponent: &Container,
+        view: &BattleView,
+        _rng: &mut dyn RngCore,
+    ) -> Option<Action> {
+        let mut best = None;
+        for action in actions(pokemon) {
+            let score = worst_reply(pokemon, action, opponent, view, self.depth.max(1));
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((action, score));
+            }
+        }
+        best.map(|(action, _)| action)
+    }
+}
+
+/// The strategies `battle --ai1` and `--ai2` can pick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AiStrategy {
+    #[default]
+    Random,
+    Greedy,
+    Defensive,
+    Lookahead,
+}
+
+impl AiStrategy {
+    pub fn build(self) -> Box<dyn BattleAi> {
+        match self {
+            AiStrategy::Random => Box::new(RandomAi),
+            AiStrategy::Greedy => Box::new(GreedyAi),
+            AiStrategy::Defensive => Box::new(DefensiveAi),
+            AiStrategy::Lookahead => Box::new(LookaheadAi {
+                depth: LOOKAHEAD_DEPTH,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for AiStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiStrategy::Random => write!(f, "random"),
+            AiStrategy::Greedy => write!(f, "greedy"),
+            AiStrategy::Defensive => write!(f, "defensive"),
+            AiStrategy::Lookahead => write!(f, "lookahead"),
+        }
+    }
+}
+
+impl FromStr for AiStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(AiStrategy::Random),
+            "greedy" => Ok(AiStrategy::Greedy),
+            "defensive" => Ok(AiStrategy::Defensive),
+            "lookahead" => Ok(AiStrategy::Lookahead),
+            _ => Err(format!(
+                "Invalid AI: {} (expected random, greedy, defensive or lookahead)",
+                s
+            )),
+        }
+    }
+}
+
+fn usable_moves(pokemon: &Container) -> impl Iterator<Item = (usize, &Move)> {
+    pokemon
+        .moves
+        .iter()
+        .enumerate()
+        .filter(|(_, battle_move)| battle_move.is_usable())
+}
+
+/// Every action `pokemon` can take: its moves with PP left, or Struggle.
+fn actions(pokemon: &Container) -> Vec<Action> {
+    let actions: Vec<Action> = usable_moves(pokemon)
+        .map(|(index, _)| Action::Move(index))
+        .collect();
+    if actions.is_empty() {
+        vec![Action::Struggle]
+    } else {
+        actions
+    }
+}
+
+fn action_move(pokemon: &Container, action: Action) -> Move {
+    match action {
+        Action::Move(index) => pokemon.moves[index].clone(),
+        Action::Struggle => Move::struggle(),
+    }
+}
+
+fn hp_fraction(pokemon: &Container) -> f64 {
+    pokemon.hp.max(0) as f64 / pokemon.max_hp.max(1) as f64
+}
+
+/// The damage `battle_move` does on average: the middle damage roll without a
+/// critical hit, times the chance to hit.
+pub fn expected_damage(
+    attacker: &Container,
+    defender: &Container,
+    battle_move: &Move,
+    view: &BattleView,
+) -> f64 {
+    let roll = (MIN_DAMAGE_ROLL + 1.0) / 2.0;
+    let damage = Battle::calculate_damage(
+        attacker,
+        defender,
+        battle_move,
+        view.chart,
+        view.weather,
+        roll,
+        false,
+    );
+    damage.amount as f64 * Battle::hit_chance(attacker, defender, battle_move)
+}
+
+/// The usable move with the highest expected damage (the first one on a tie),
+/// or Struggle.
+fn strongest_attack(pokemon: &Container, opponent: &Container, view: &BattleView) -> Action {
+    let mut best: Option<(usize, f64)> = None;
+    for (index, battle_move) in usable_moves(pokemon) {
+        let damage = expected_damage(pokemon, opponent, battle_move, view);
+        if best.is_none_or(|(_, best_damage)| damage > best_damage) {
+            best = Some((index, damage));
+        }
+    }
+    best.map_or(Action::Struggle, |(index, _)| Action::Move(index))
+}
+
+fn has_useful_effect(
+    battle_move: &Move,
+    pokemon: &Container,
+    opponent: &Container,
+    view: &BattleView,
+) -> bool {
+    let Some(effect) = &battle_move.effect else {
+        return false;
+    };
+    match effect.effect_type {
+        EffectType::StatusCondition(_) => {
+            Battle::status_to_inflict(battle_move, opponent).is_some()
+        }
+        EffectType::StatChange { stat, stages } if stages > 0 => {
+            pokemon.stat_stages.get(stat) < StatStages::MAX_STAGE
+        }
+        EffectType::StatChange { stat, .. } => {
+            opponent.stat_stages.get(stat) > StatStages::MIN_STAGE
+        }
+        EffectType::WeatherChange(weather) => view.weather != Some(weather),
+    }
+}
+
+/// The score `pokemon` can count on after taking `action`, whatever the
+/// opponent answers with, looking `depth` turns ahead in all.
+fn worst_reply(
+    pokemon: &Container,
+    action: Action,
+    opponent: &Container,
+    view: &BattleView,
+    depth: u32,
+) -> f64 {
+    actions(opponent)
+        .into_iter()
+        .map(|reply| {
+            let (pokemon, opponent) = play_turn(pokemon, action, opponent, reply, view);
+            best_line(&pokemon, &opponent, view, depth - 1)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn best_line(pokemon: &Container, opponent: &Container, view: &BattleView, depth: u32) -> f64 {
+    if depth == 0 || pokemon.hp <= 0 || opponent.hp <= 0 {
+        return hp_fraction(pokemon) - hp_fraction(opponent);
+    }
+    actions(pokemon)
+        .into_iter()
+        .map(|action| worst_reply(pokemon, action, opponent, view, depth))
+        .fold(f64::NEG_INFINITY, f64::max)
+}
+
+/// Plays a turn on copies of the Pokémon with expected damage instead of rolls:
+/// PP is spent, stat changes that always happen are applied and Struggle costs
+/// its recoil. Statuses and weather are left as they are.
+fn play_turn(
+    pokemon: &Container,
+    action: Action,
+    opponent: &Container,
+    reply: Action,
+    view: &BattleView,
+) -> (Container, Container) {
+    let (mut pokemon, mut opponent) = (pokemon.clone(), opponent.clone());
+    let pokemon_move = action_move(&pokemon, action);
+    let opponent_move = action_move(&opponent, reply);
+    let order = Battle::turn_order(&pokemon, &pokemon_move, &opponent, &opponent_move, true);
+    let mut turn = [
+        (&mut pokemon, action, pokemon_move),
+        (&mut opponent, reply, opponent_move),
+    ];
+    if order == Side::Opponent {
+        turn.swap(0, 1);
+    }
+    let [(first, first_action, first_move), (second, second_action, second_move)] = turn;
+    simulate_move(first, second, first_action, &first_move, view);
+    if second.hp > 0 {
+        simulate_move(second, first, second_action, &second_move, view);
+    }
+    (pokemon, opponent)
+}
+
+fn simulate_move(
+    attacker: &mut Container,
+    defender: &mut Container,
+    action: Action,
+    battle_move: &Move,
+    view: &BattleView,
+) {
+    if let Action::Move(index) = action {
+        let _ = attacker.moves[index].use_move();
+    }
+    let damage = expected_damage(attacker, defender, battle_move, view).round() as i32;
+    defender.hp = (defender.hp - damage).max(0);
+    if let Some(effect) = battle_move
+        .effect
+        .as_ref()
+        .filter(|effect| effect.chance >= 100)
+    {
+        if let EffectType::StatChange { stat, stages } = effect.effect_type {
+            if stages > 0 {
+                attacker.stat_stages.change(stat, stages);
+            } else {
+                defender.stat_stages.change(stat, stages);
+            }
+        }
+    }
+    if battle_move.is_struggle() {
+        attacker.hp = (attacker.hp - (attacker.max_hp / STRUGGLE_RECOIL_DIVISOR).max(1)).max(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::{MoveEffect, PokemonType, Stat};
+    use crate::replay::BattleEvent;
+    use rand::SeedableRng;
+
+    fn pokemon(name: &str, hp: i32, speed: u32, pokemon_type: PokemonType) -> Container {
+        let mut pokemon = Container::new(name, "kanto", 30, hp, 60, 60, speed, pokemon_type);
+        pokemon.sp_attack = 60;
+        pokemon
+    }
+
+    fn attack(name: &str, power: u32, accuracy: u8, pokemon_type: PokemonType) -> Move {
+        Move::new(
+            name,
+            power,
+            accuracy,
+            10,
+            pokemon_type,
+            MoveCategory::Physical,
+            "",
+            None,
+        )
+    }
+
+    fn growl() -> Move {
+        Move::new(
+            "Growl",
+            0,
+            100,
+            40,
+            PokemonType::Normal,
+            MoveCategory::Status,
+            "",
+            Some(MoveEffect {
+                effect_type: EffectType::StatChange {
+                    stat: Stat::Attack,
+                    stages: -1,
+                },
+                chance: 100,
+                turns: None,
+            }),
+        )
+    }
+
+    fn choose(ai: &mut dyn BattleAi, pokemon: &Container, opponent: &Container) -> Action {
+        let chart = TypeEffectiveness::new();
+        let view = BattleView {
+            chart: &chart,
+            weather: None,
+        };
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        ai.choose_action(pokemon, opponent, &view, &mut rng)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_greedy_and_defensive_choices() {
+        let mut squirtle = pokemon("Squirtle", 100, 40, PokemonType::Water);
+        squirtle.learn_move(growl());
+        squirtle.learn_move(attack("Tackle", 40, 100, PokemonType::Normal));
+        squirtle.learn_move(attack("Water Gun", 40, 100, PokemonType::Water));
+        squirtle.learn_move(attack("Hydro Pump", 110, 20, PokemonType::Water));
+        let charmander = pokemon("Charmander", 100, 60, PokemonType::Fire);
+
+        // Water Gun is super effective; Hydro Pump hits harder but rarely.
+        assert_eq!(
+            choose(&mut GreedyAi, &squirtle, &charmander),
+            Action::Move(2)
+        );
+
+        // On even HP it attacks, but when ahead it uses Growl until the
+        // opponent's attack can't drop any further.
+        assert_eq!(
+            choose(&mut DefensiveAi, &squirtle, &charmander),
+            Action::Move(2)
+        );
+        let mut hurt = charmander.clone();
+        hurt.hp = 50;
+        assert_eq!(choose(&mut DefensiveAi, &squirtle, &hurt), Action::Move(0));
+        hurt.stat_stages.change(Stat::Attack, -6);
+        assert_eq!(choose(&mut DefensiveAi, &squirtle, &hurt), Action::Move(2));
+
+        squirtle.moves.iter_mut().for_each(|m| m.pp = 0);
+        assert_eq!(
+            choose(&mut GreedyAi, &squirtle, &charmander),
+            Action::Struggle
+        );
+        assert_eq!(
+            choose(&mut RandomAi, &squirtle, &charmander),
+            Action::Struggle
+        );
+    }
+
+    #[test]
+    fn test_defensive_status_moves_work_on_immune_types() {
+        // Growl is a Normal move, but the engine lets it through to a Ghost, so
+        // the defensive AI uses it until the opponent's attack bottoms out and
+        // then attacks, instead of wasting every turn on it.
+        let mut meowth = pokemon("Meowth", 200, 90, PokemonType::Normal);
+        meowth.learn_move(growl());
+        meowth.learn_move(attack("Bite", 60, 100, PokemonType::Dark));
+        let mut gastly = pokemon("Gastly", 100, 80, PokemonType::Ghost);
+        gastly.hp = 60;
+        gastly.learn_move(attack("Tackle", 40, 100, PokemonType::Normal));
+        for pokemon in [&mut meowth, &mut gastly] {
+            pokemon.state = crate::container::ContainerState::Running;
+        }
+
+        let record = Battle::start_battle(
+            &mut meowth,
+            &mut gastly,
+            None,
+            &mut Battle::seeded_rng(3),
+            None,
+            [&mut DefensiveAi, &mut RandomAi],
+        );
+        assert!(record.challenger_won());
+        assert_eq!(record.challenger_moves["Growl"], 6);
+        assert!(record.challenger_moves["Bite"] > 0);
+        assert!(!record
+            .events
+            .iter()
+            .any(|event| matches!(event, BattleEvent::NoEffect { .. })));
+    }
+
+    #[test]
+    fn test_lookahead_finishes_off_before_being_knocked_out() {
+        // The opponent is faster and knocks the lookahead Pokémon out with its
+        // next hit, so the weaker priority move that finishes the opponent off
+        // now beats the stronger move that would come too late.
+        let mut rattata = pokemon("Rattata", 40, 50, PokemonType::Normal);
+        rattata.hp = 10;
+        rattata.learn_move(attack("Hyper Fang", 80, 100, PokemonType::Normal));
+        let mut quick_attack = attack("Quick Attack", 40, 100, PokemonType::Normal);
+        quick_attack.priority = 1;
+        rattata.learn_move(quick_attack);
+        let mut pidgey = pokemon("Pidgey", 100, 90, PokemonType::Flying);
+        pidgey.hp = 10;
+        pidgey.learn_move(attack("Gust", 40, 100, PokemonType::Flying));
+
+        assert_eq!(choose(&mut GreedyAi, &rattata, &pidgey), Action::Move(0));
+        let mut lookahead = LookaheadAi {
+            depth: LOOKAHEAD_DEPTH,
+        };
+        assert_eq!(choose(&mut lookahead, &rattata, &pidgey), Action::Move(1));
+    }
+
+    #[test]
+    fn test_strategies_parse() {
+        for strategy in [
+            AiStrategy::Random,
+            AiStrategy::Greedy,
+            AiStrategy::Defensive,
+            AiStrategy::Lookahead,
+        ] {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+        }
+        assert_eq!("Greedy".parse(), Ok(AiStrategy::Greedy));
+        assert!("minimax".parse::<AiStrategy>().is_err());
+    }
+}
@@ -1,60 +1,294 @@
+use crate::ai::{BattleAi, BattleView};
 use crate::container::Container;
 use crate::evolution::EvolutionManager;
-use crate::stats::BattleRecord;
+use crate::moves::{
+    EffectType, Move, MoveCategory, Stat, StatStages, StatusCondition, TypeEffectiveness, Weather,
+};
+use crate::replay::{BattleEvent, BattleLog};
+use crate::stats::{BattleRecord, PokemonStats};
 use chrono::Utc;
-use rand::Rng;
-use colored::Colorize;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Damage multiplier for a move that shares the attacker's type.
+pub const STAB_MULTIPLIER: f32 = 1.5;
+pub const CRITICAL_MULTIPLIER: f32 = 1.5;
+/// One in this many hits is critical.
+pub const CRITICAL_HIT_ODDS: u32 = 24;
+/// Lowest random roll applied to damage; the highest is 1.0.
+pub const MIN_DAMAGE_ROLL: f32 = 0.85;
+/// Struggle's recoil is this fraction of the user's maximum HP.
+pub const STRUGGLE_RECOIL_DIVISOR: i32 = 4;
+/// Turns after which a battle ends with the Pokémon that has the larger share of
+/// its HP left as the winner, so two Pokémon that can't hurt each other don't
+/// battle forever.
+pub const MAX_TURNS: u32 = 100;
+/// A burned Pokémon loses this fraction of its maximum HP at the end of each turn.
+pub const BURN_DAMAGE_DIVISOR: i32 = 16;
+/// A poisoned Pokémon loses this fraction of its maximum HP at the end of each turn.
+pub const POISON_DAMAGE_DIVISOR: i32 = 8;
+/// A paralyzed Pokémon can't move one turn in this many.
+pub const PARALYSIS_SKIP_ODDS: u32 = 4;
+/// A frozen Pokémon thaws out one turn in this many.
+pub const THAW_ODDS: u32 = 5;
+/// Sleep lasts between 1 and this many turns unless the move says otherwise.
+pub const MAX_SLEEP_TURNS: u8 = 3;
+/// Turns that weather started by a move lasts unless the move says otherwise.
+pub const WEATHER_TURNS: u8 = 5;
+/// Sandstorm and hail take this fraction of a Pokémon's maximum HP each turn.
+pub const WEATHER_DAMAGE_DIVISOR: i32 = 16;
+
+/// The outcome of one hit, as computed by `Battle::calculate_damage`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Damage {
+    pub amount: u32,
+    /// Type effectiveness of the move against the defender.
+    pub effectiveness: f32,
+    pub critical: bool,
+}
+
+/// One of the two Pokémon in a battle: the challenger is the first one passed to
+/// `Battle::start_battle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Challenger,
+    Opponent,
+}
+
+/// What a Pokémon does on its turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Use the move at this index in the Pokémon's move list.
+    Move(usize),
+    /// No move has PP left (or the Pokémon knows none), so it struggles.
+    Struggle,
+}
+
+/// The weather during a battle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveWeather {
+    pub weather: Weather,
+    /// Turns left before the weather clears, or `None` if it lasts the whole
+    /// battle, as weather chosen with `battle --weather` does.
+    pub turns_left: Option<u8>,
+}
+
+/// A Pokémon taking part in a battle, with the state that only lasts as long as
+/// the battle does.
+struct Fighter<'a> {
+    pokemon: &'a mut Container,
+    side: Side,
+    /// Turns the Pokémon stays asleep before it wakes up.
+    sleep_turns: u8,
+    /// Whether its fainting has been reported.
+    fainted: bool,
+}
+
+impl<'a> Fighter<'a> {
+    /// A Pokémon that comes into the battle asleep sleeps for a fresh number of
+    /// turns, since the counter isn't kept between battles.
+    fn new(pokemon: &'a mut Container, side: Side, rng: &mut impl Rng) -> Self {
+        let sleep_turns = if pokemon.status_condition == Some(StatusCondition::Sleep) {
+            rng.gen_range(1..=MAX_SLEEP_TURNS)
+        } else {
+            0
+        };
+        Self {
+            pokemon,
+            side,
+            sleep_turns,
+            fainted: false,
+        }
+    }
+
+    /// Takes `amount` HP and returns it with the HP left, for the battle log.
+    fn lose_hp(&mut self, amount: i32) -> (u32, i32) {
+        self.pokemon.hp = (self.pokemon.hp - amount).max(0);
+        self.pokemon.stats.total_damage_taken += amount as u32;
+        (amount as u32, self.pokemon.hp)
+    }
+
+    /// Reports the Pokémon fainting the first time it's found with no HP.
+    fn check_faint(&mut self, log: &mut BattleLog) {
+        if self.pokemon.hp == 0 && !self.fainted {
+            self.fainted = true;
+            log.emit(BattleEvent::Faint { side: self.side });
+        }
+    }
+}
 
 pub struct Battle;
 
 impl Battle {
-    #[allow(dead_code)]
+    /// EXP the winner earns for beating a Pokémon of `opponent_level`.
     pub fn calculate_exp_reward(opponent_level: u32) -> u32 {
         (opponent_level * 10) + 100
     }
 
+    /// The random number generator for a battle with `seed`. Every random choice
+    /// in a battle comes from it, so the same seed and the same Pokémon give the
+    /// same battle.
+    pub fn seeded_rng(seed: u64) -> StdRng {
+        StdRng::seed_from_u64(seed)
+    }
+
+    /// Battles `pokemon1` (the challenger) against `pokemon2` until one faints or
+    /// `MAX_TURNS` runs out. Every random choice comes from `rng`, and everything
+    /// that happens is printed and kept in the record's `events`. A winner that
+    /// levels up evolves if `evolution_manager` has an evolution for it. `ais`
+    /// pick the challenger's and the opponent's moves; a side whose AI forfeits
+    /// loses.
     pub fn start_battle(
         pokemon1: &mut Container,
         pokemon2: &mut Container,
-        _evolution_managerr: &EvolutionManager,
-    ) {
+        weather: Option<Weather>,
+        rng: &mut impl Rng,
+        evolution_manager: Option<&EvolutionManager>,
+        mut ais: [&mut dyn BattleAi; 2],
+    ) -> BattleRecord {
         let mut turns = 0;
-        println!(
-            "{}",
-            "╔══════════════════════════════════════╗".bright_red()
-        );
-        println!(
-            "{}",
-            format!(
-                "║ ⚔️  Battle: {} vs {} ║",
-                pokemon1.name.bright_yellow(),
-                pokemon2.name.bright_yellow()
-            )
-            .bright_red()
-            .bold()
-        );
-        println!(
-            "{}",
-            "╠══════════════════════════════════════╣".bright_red()
-        );
+        let before1 = pokemon1.stats.clone();
+        let before2 = pokemon2.stats.clone();
+        let mut log = BattleLog::new();
+        log.emit(BattleEvent::Start {
+            challenger: pokemon1.name.clone(),
+            opponent: pokemon2.name.clone(),
+            weather,
+        });
 
-        let mut rng = rand::thread_rng();
+        let chart = TypeEffectiveness::new();
+        let mut weather = weather.map(|weather| ActiveWeather {
+            weather,
+            turns_left: None,
+        });
 
-        while pokemon1.is_active() && pokemon2.is_active() {
+        let mut fighters = [
+            Fighter::new(pokemon1, Side::Challenger, rng),
+            Fighter::new(pokemon2, Side::Opponent, rng),
+        ];
+        let mut forfeited = None;
+        while fighters.iter().all(|f| f.pokemon.is_active()) && turns < MAX_TURNS {
             turns += 1;
+            log.emit(BattleEvent::TurnStart { turn: turns });
+
+            let [challenger, opponent] = &mut fighters;
+            let [challenger_ai, opponent_ai] = &mut ais;
+            let view = BattleView {
+                chart: &chart,
+                weather: weather.map(|active| active.weather),
+            };
+            let Some(challenger_action) =
+                challenger_ai.choose_action(challenger.pokemon, opponent.pokemon, &view, rng)
+            else {
+                forfeited = Some(Side::Challenger);
+                break;
+            };
+            let Some(opponent_action) =
+                opponent_ai.choose_action(opponent.pokemon, challenger.pokemon, &view, rng)
+            else {
+                forfeited = Some(Side::Opponent);
+                break;
+            };
+            let first = Self::turn_order(
+                challenger.pokemon,
+                &Self::action_move(challenger.pokemon, challenger_action),
+                opponent.pokemon,
+                &Self::action_move(opponent.pokemon, opponent_action),
+                rng.gen(),
+            );
 
-            if pokemon1.is_active() {
-                let move_index = rng.gen_range(0..pokemon1.moves.len());
-                Self::execute_move(pokemon1, pokemon2, move_index);
+            let (attacker, defender, attacker_action, defender_action) = match first {
+                Side::Challenger => (challenger, opponent, challenger_action, opponent_action),
+                Side::Opponent => (opponent, challenger, opponent_action, challenger_action),
+            };
+            Self::execute_action(
+                attacker,
+                defender,
+                attacker_action,
+                &chart,
+                &mut weather,
+                &mut log,
+                rng,
+            );
+            if attacker.pokemon.is_active() && defender.pokemon.is_active() {
+                Self::execute_action(
+                    defender,
+                    attacker,
+                    defender_action,
+                    &chart,
+                    &mut weather,
+                    &mut log,
+                    rng,
+                );
             }
+            if attacker.pokemon.is_active() && defender.pokemon.is_active() {
+                if let Some(active) = weather {
+                    Self::apply_weather_damage(attacker, active.weather, &mut log);
+                    Self::apply_weather_damage(defender, active.weather, &mut log);
+                }
+                Self::apply_status_damage(attacker, &mut log);
+                Self::apply_status_damage(defender, &mut log);
+                Self::tick_weather(&mut weather, &mut log);
+            }
+            attacker.check_faint(&mut log);
+            defender.check_faint(&mut log);
+        }
 
-            if pokemon2.is_active() {
-                let move_index = rng.gen_range(0..pokemon2.moves.len());
-                Self::execute_move(pokemon2, pokemon1, move_index);
+        // Stat changes wear off after the battle, and fainting cures a Pokémon's
+        // status.
+        for pokemon in [&mut *pokemon1, &mut *pokemon2] {
+            pokemon.stat_stages = StatStages::default();
+            if pokemon.hp == 0 {
+                pokemon.status_condition = None;
             }
         }
 
-        let (winner, loser) = if pokemon1.is_active() {
+        if let Some(side) = forfeited {
+            log.emit(BattleEvent::Forfeit { side });
+        }
+        let challenger_won = if let Some(side) = forfeited {
+            side == Side::Opponent
+        } else if pokemon1.is_active() && pokemon2.is_active() {
+            log.emit(BattleEvent::OutOfTurns);
+            pokemon1.hp as i64 * pokemon2.max_hp as i64
+                >= pokemon2.hp as i64 * pokemon1.max_hp as i64
+        } else {
+            pokemon1.is_active()
+        };
+        let winner_side = if challenger_won {
+            Side::Challenger
+        } else {
+            Side::Opponent
+        };
+        log.emit(BattleEvent::End {
+            winner: winner_side,
+            turns,
+        });
+
+        let battle_record = BattleRecord {
+            id: String::new(),
+            winner: String::new(),
+            loser: String::new(),
+            date: Utc::now(),
+            turns,
+            challenger_id: pokemon1.id.clone(),
+            opponent_id: pokemon2.id.clone(),
+            winner_id: String::new(),
+            challenger_type: pokemon1.pokemon_type,
+            opponent_type: pokemon2.pokemon_type,
+            challenger_damage: pokemon1.stats.total_damage_dealt - before1.total_damage_dealt,
+            opponent_damage: pokemon2.stats.total_damage_dealt - before2.total_damage_dealt,
+            challenger_moves: Self::moves_used_since(pokemon1, &before1),
+            opponent_moves: Self::moves_used_since(pokemon2, &before2),
+            seed: None,
+            events: Vec::new(),
+        };
+
+        let (winner, loser) = if challenger_won {
             pokemon1.stats.battles_won += 1;
             pokemon2.stats.battles_lost += 1;
             (pokemon1, pokemon2)
@@ -64,46 +298,1297 @@ impl Battle {
             (pokemon2, pokemon1)
         };
 
-        let _battle_record = BattleRecord {
+        // Beating a Pokémon that had already fainted or that gave up isn't worth
+        // anything.
+        let level_before = winner.level;
+        if turns > 0 && forfeited.is_none() {
+            let exp = Self::calculate_exp_reward(loser.level);
+            winner.gain_exp(exp);
+            log.emit(BattleEvent::ExpGained {
+                side: winner_side,
+                amount: exp,
+            });
+        }
+        for level in level_before + 1..=winner.level {
+            log.emit(BattleEvent::LevelUp {
+                side: winner_side,
+                level,
+            });
+        }
+        let evolution = evolution_manager
+            .filter(|_| winner.level > level_before)
+            .and_then(|manager| manager.get_evolution(&winner.name));
+        if let Some(evolution) = evolution {
+            let from = winner.name.clone();
+            if winner.evolve(evolution) {
+                log.emit(BattleEvent::Evolved {
+                    side: winner_side,
+                    from,
+                    to: winner.name.clone(),
+                });
+            }
+        }
+
+        BattleRecord {
             winner: winner.name.clone(),
             loser: loser.name.clone(),
-            date: Utc::now(),
-            turns,
+            winner_id: winner.id.clone(),
+            events: log.into_events(),
+            ..battle_record
+        }
+    }
+
+    fn moves_used_since(pokemon: &Container, before: &PokemonStats) -> HashMap<String, u32> {
+        pokemon
+            .stats
+            .moves_used
+            .iter()
+            .map(|(name, count)| {
+                let previous = before.moves_used.get(name).copied().unwrap_or(0);
+                (name.clone(), count - previous)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Picks a random move that still has PP, or Struggle if there is none.
+    pub fn choose_action(pokemon: &Container, rng: &mut impl Rng) -> Action {
+        let usable: Vec<usize> = (0..pokemon.moves.len())
+            .filter(|&i| pokemon.moves[i].is_usable())
+            .collect();
+        if usable.is_empty() {
+            Action::Struggle
+        } else {
+            Action::Move(usable[rng.gen_range(0..usable.len())])
+        }
+    }
+
+    fn action_move(pokemon: &Container, action: Action) -> Move {
+        match action {
+            Action::Move(index) => pokemon.moves[index].clone(),
+            Action::Struggle => Move::struggle(),
+        }
+    }
+
+    /// A Pokémon's speed for deciding turn order, after its Speed stage. Paralysis
+    /// halves it.
+    pub fn effective_speed(pokemon: &Container) -> u32 {
+        let speed =
+            (pokemon.speed as f32 * StatStages::multiplier(pokemon.stat_stages.speed)) as u32;
+        if pokemon.status_condition == Some(StatusCondition::Paralysis) {
+            speed / 2
+        } else {
+            speed
+        }
+    }
+
+    /// The chance, from 0 to 1, that `battle_move` hits: its accuracy scaled by the
+    /// attacker's accuracy stage minus the defender's evasion stage.
+    pub fn hit_chance(attacker: &Container, defender: &Container, battle_move: &Move) -> f64 {
+        let stage = attacker.stat_stages.accuracy - defender.stat_stages.evasion;
+        let accuracy =
+            battle_move.accuracy.min(100) as f32 * StatStages::accuracy_multiplier(stage);
+        (accuracy as f64 / 100.0).min(1.0)
+    }
+
+    /// Decides who acts first in a turn where the challenger uses `challenger_move`
+    /// and the opponent uses `opponent_move`: the move with the higher priority
+    /// goes first, then the faster Pokémon (see `effective_speed`), and `tie_break`
+    /// picks the challenger when both are equally fast.
+    pub fn turn_order(
+        challenger: &Container,
+        challenger_move: &Move,
+        opponent: &Container,
+        opponent_move: &Move,
+        tie_break: bool,
+    ) -> Side {
+        let challenger_first = match challenger_move
+            .priority
+            .cmp(&opponent_move.priority)
+            .then(Self::effective_speed(challenger).cmp(&Self::effective_speed(opponent)))
+        {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => tie_break,
+        };
+        if challenger_first {
+            Side::Challenger
+        } else {
+            Side::Opponent
+        }
+    }
+
+    /// Computes the damage `battle_move` does when `attacker` uses it on `defender`,
+    /// using the main-series formula:
+    ///
+    /// ((2 × level / 5 + 2) × power × attack / defense / 50 + 2) × modifiers
+    ///
+    /// where the modifiers are STAB, type effectiveness, weather, a critical hit
+    /// and the random `roll` (between `MIN_DAMAGE_ROLL` and 1.0). Physical moves
+    /// use attack against defense and special moves special attack against
+    /// special defense, each scaled by its stat stage; a critical hit ignores the
+    /// attacker's drops and the defender's boosts. A burned attacker does half
+    /// damage with physical moves. Struggle has no type, so it gets neither STAB
+    /// nor type effectiveness. Status moves, moves without power and moves the
+    /// defender is immune to do no damage; anything else does at least 1.
+    pub fn calculate_damage(
+        attacker: &Container,
+        defender: &Container,
+        battle_move: &Move,
+        chart: &TypeEffectiveness,
+        weather: Option<Weather>,
+        roll: f32,
+        critical: bool,
+    ) -> Damage {
+        let typeless = battle_move.is_struggle();
+        let effectiveness = if typeless {
+            1.0
+        } else {
+            chart.get_dual_multiplier(
+                battle_move.pokemon_type,
+                defender.pokemon_type,
+                defender.secondary_type,
+            )
+        };
+        if battle_move.category == MoveCategory::Status
+            || battle_move.power == 0
+            || effectiveness == 0.0
+        {
+            return Damage {
+                amount: 0,
+                effectiveness,
+                critical: false,
+            };
+        }
+
+        let level_factor = 2.0 * attacker.level as f32 / 5.0 + 2.0;
+        let (attack, defense, attack_stat, defense_stat) = match battle_move.category {
+            MoveCategory::Special => (
+                attacker.sp_attack,
+                defender.sp_defense,
+                Stat::SpecialAttack,
+                Stat::SpecialDefense,
+            ),
+            _ => (
+                attacker.attack,
+                defender.defense,
+                Stat::Attack,
+                Stat::Defense,
+            ),
+        };
+        let mut attack_stage = attacker.stat_stages.get(attack_stat);
+        let mut defense_stage = defender.stat_stages.get(defense_stat);
+        if critical {
+            attack_stage = attack_stage.max(0);
+            defense_stage = defense_stage.min(0);
+        }
+        let attack = attack.max(1) as f32 * StatStages::multiplier(attack_stage);
+        let defense = defense.max(1) as f32 * StatStages::multiplier(defense_stage);
+        let ratio = attack / defense;
+        let base = level_factor * battle_move.power as f32 * ratio / 50.0 + 2.0;
+
+        let mut modifier = effectiveness * roll;
+        if !typeless && attacker.has_type(battle_move.pokemon_type) {
+            modifier *= STAB_MULTIPLIER;
+        }
+        if let (false, Some(weather)) = (typeless, weather) {
+            modifier *= weather.damage_multiplier(battle_move.pokemon_type);
+        }
+        if critical {
+            modifier *= CRITICAL_MULTIPLIER;
+        }
+        if attacker.status_condition == Some(StatusCondition::Burn)
+            && battle_move.category == MoveCategory::Physical
+        {
+            modifier *= 0.5;
+        }
+
+        Damage {
+            amount: ((base * modifier) as u32).max(1),
+            effectiveness,
+            critical,
+        }
+    }
+
+    /// The status `battle_move` would inflict on `defender` if its effect
+    /// triggers. A Pokémon can only have one status, and some types are immune to
+    /// some statuses (see `StatusCondition::immune_types`).
+    pub fn status_to_inflict(battle_move: &Move, defender: &Container) -> Option<StatusCondition> {
+        let EffectType::StatusCondition(status) = battle_move.effect.as_ref()?.effect_type else {
+            return None;
+        };
+        let immune = status
+            .immune_types()
+            .iter()
+            .any(|&immune_type| defender.has_type(immune_type));
+        if defender.status_condition.is_some() || immune {
+            None
+        } else {
+            Some(status)
+        }
+    }
+
+    /// Whether a Pokémon's status lets it act this turn: a sleeping Pokémon counts
+    /// down its sleep, a frozen one may thaw out and a paralyzed one may be
+    /// unable to move.
+    fn can_act(fighter: &mut Fighter, log: &mut BattleLog, rng: &mut impl Rng) -> bool {
+        let side = fighter.side;
+        let Some(status) = fighter.pokemon.status_condition else {
+            return true;
+        };
+        let cured = match status {
+            StatusCondition::Sleep if fighter.sleep_turns == 0 => true,
+            StatusCondition::Sleep => {
+                fighter.sleep_turns -= 1;
+                false
+            }
+            StatusCondition::Freeze => rng.gen_ratio(1, THAW_ODDS),
+            StatusCondition::Paralysis if rng.gen_ratio(1, PARALYSIS_SKIP_ODDS) => {
+                log.emit(BattleEvent::StatusPrevented { side, status });
+                return false;
+            }
+            _ => return true,
+        };
+        if cured {
+            fighter.pokemon.status_condition = None;
+            log.emit(BattleEvent::StatusCured { side, status });
+        } else {
+            log.emit(BattleEvent::StatusPrevented { side, status });
+        }
+        cured
+    }
+
+    fn execute_action(
+        attacker: &mut Fighter,
+        defender: &mut Fighter,
+        action: Action,
+        chart: &TypeEffectiveness,
+        weather: &mut Option<ActiveWeather>,
+        log: &mut BattleLog,
+        rng: &mut impl Rng,
+    ) {
+        if !Self::can_act(attacker, log, rng) {
+            return;
+        }
+        let (user_side, target_side) = (attacker.side, defender.side);
+        let user = &mut *attacker.pokemon;
+        let battle_move = match action {
+            Action::Move(index) => {
+                let Some(battle_move) = user.moves.get_mut(index) else {
+                    return;
+                };
+                if battle_move.use_move().is_err() {
+                    return;
+                }
+                battle_move.clone()
+            }
+            Action::Struggle => {
+                log.emit(BattleEvent::Struggle { side: user_side });
+                Move::struggle()
+            }
+        };
+        let count = user
+            .stats
+            .moves_used
+            .entry(battle_move.name.clone())
+            .or_insert(0);
+        *count += 1;
+
+        log.emit(BattleEvent::MoveUsed {
+            side: user_side,
+            move_name: battle_move.name.clone(),
+        });
+        if !rng.gen_bool(Self::hit_chance(user, defender.pokemon, &battle_move)) {
+            log.emit(BattleEvent::Miss { side: user_side });
+            return;
+        }
+
+        let roll = rng.gen_range(MIN_DAMAGE_ROLL..=1.0);
+        let critical = rng.gen_ratio(1, CRITICAL_HIT_ODDS);
+        let damage = Self::calculate_damage(
+            user,
+            defender.pokemon,
+            &battle_move,
+            chart,
+            weather.map(|active| active.weather),
+            roll,
+            critical,
+        );
+
+        // Type immunities only stop moves that deal damage: Growl still works on a
+        // Ghost, and status immunities are left to `status_to_inflict`.
+        if damage.effectiveness == 0.0 && battle_move.category != MoveCategory::Status {
+            log.emit(BattleEvent::NoEffect { side: target_side });
+            return;
+        }
+        if damage.amount > 0 {
+            if damage.critical {
+                log.emit(BattleEvent::Critical);
+            }
+            if damage.effectiveness != 1.0 {
+                log.emit(BattleEvent::Effectiveness {
+                    multiplier: damage.effectiveness,
+                });
+            }
+            user.stats.total_damage_dealt += damage.amount;
+            let (amount, hp) = defender.lose_hp(damage.amount as i32);
+            log.emit(BattleEvent::Damage {
+                side: target_side,
+                amount,
+                hp,
+            });
+        }
+
+        let target = &mut *defender.pokemon;
+        if let Some(effect) = &battle_move.effect {
+            if rng.gen_ratio(u32::from(effect.chance.min(100)), 100) {
+                match effect.effect_type {
+                    EffectType::StatusCondition(_) if target.hp > 0 => {
+                        if let Some(status) = Self::status_to_inflict(&battle_move, target) {
+                            target.status_condition = Some(status);
+                            if status == StatusCondition::Sleep {
+                                defender.sleep_turns = effect
+                                    .turns
+                                    .unwrap_or_else(|| rng.gen_range(1..=MAX_SLEEP_TURNS));
+                            }
+                            log.emit(BattleEvent::StatusInflicted {
+                                side: target_side,
+                                status,
+                            });
+                        }
+                    }
+                    // Raising a stat helps the user and lowering one hinders the
+                    // target, as with Swords Dance and Growl.
+                    EffectType::StatChange { stat, stages } if stages > 0 => {
+                        let changed = user.stat_stages.change(stat, stages);
+                        log.emit(BattleEvent::StatChanged {
+                            side: user_side,
+                            stat,
+                            stages,
+                            changed,
+                        });
+                    }
+                    EffectType::StatChange { stat, stages } if target.hp > 0 => {
+                        let changed = target.stat_stages.change(stat, stages);
+                        log.emit(BattleEvent::StatChanged {
+                            side: target_side,
+                            stat,
+                            stages,
+                            changed,
+                        });
+                    }
+                    EffectType::WeatherChange(new_weather) => {
+                        if weather.is_some_and(|active| active.weather == new_weather) {
+                            log.emit(BattleEvent::MoveFailed);
+                        } else {
+                            *weather = Some(ActiveWeather {
+                                weather: new_weather,
+                                turns_left: Some(effect.turns.unwrap_or(WEATHER_TURNS)),
+                            });
+                            log.emit(BattleEvent::WeatherStarted {
+                                weather: new_weather,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        defender.check_faint(log);
+
+        if battle_move.is_struggle() {
+            let recoil = (attacker.pokemon.max_hp / STRUGGLE_RECOIL_DIVISOR).max(1);
+            let (amount, hp) = attacker.lose_hp(recoil);
+            log.emit(BattleEvent::Recoil {
+                side: user_side,
+                amount,
+                hp,
+            });
+            attacker.check_faint(log);
+        }
+    }
+
+    /// End-of-turn damage from sandstorm and hail, which spare some types.
+    fn apply_weather_damage(fighter: &mut Fighter, weather: Weather, log: &mut BattleLog) {
+        let Some(immune) = weather.chip_immune_types() else {
+            return;
+        };
+        if immune
+            .iter()
+            .any(|&immune_type| fighter.pokemon.has_type(immune_type))
+        {
+            return;
+        }
+        let (amount, hp) =
+            fighter.lose_hp((fighter.pokemon.max_hp / WEATHER_DAMAGE_DIVISOR).max(1));
+        log.emit(BattleEvent::WeatherDamage {
+            side: fighter.side,
+            weather,
+            amount,
+            hp,
+        });
+    }
+
+    /// Counts down weather started by a move and clears it when it runs out.
+    fn tick_weather(weather: &mut Option<ActiveWeather>, log: &mut BattleLog) {
+        let Some(active) = weather else {
+            return;
+        };
+        let Some(turns_left) = active.turns_left.as_mut() else {
+            return;
+        };
+        *turns_left = turns_left.saturating_sub(1);
+        if *turns_left == 0 {
+            log.emit(BattleEvent::WeatherEnded {
+                weather: active.weather,
+            });
+            *weather = None;
+        }
+    }
+
+    /// End-of-turn damage from burn and poison.
+    fn apply_status_damage(fighter: &mut Fighter, log: &mut BattleLog) {
+        let (status, divisor) = match fighter.pokemon.status_condition {
+            Some(status @ StatusCondition::Burn) => (status, BURN_DAMAGE_DIVISOR),
+            Some(status @ StatusCondition::Poison) => (status, POISON_DAMAGE_DIVISOR),
+            _ => return,
         };
+        let (amount, hp) = fighter.lose_hp((fighter.pokemon.max_hp / divisor).max(1));
+        log.emit(BattleEvent::StatusDamage {
+            side: fighter.side,
+            status,
+            amount,
+            hp,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{GreedyAi, RandomAi};
+    use crate::moves::{MoveEffect, PokemonType};
+
+    fn pokemon(level: u32, attack: u32, defense: u32, pokemon_type: PokemonType) -> Container {
+        Container::new(
+            "Test",
+            "kanto",
+            level,
+            100,
+            attack,
+            defense,
+            50,
+            pokemon_type,
+        )
+    }
+
+    fn attack(power: u32, pokemon_type: PokemonType) -> Move {
+        Move::new(
+            "Test Move",
+            power,
+            100,
+            10,
+            pokemon_type,
+            MoveCategory::Physical,
+            "",
+            None,
+        )
+    }
 
-        println!(
-            "{}",
-            format!(
-                "║ 🏆 Winner: {} in {} turns! ║",
-                winner.name.bright_green().bold(),
-                turns
+    #[test]
+    fn test_turn_order_uses_priority_then_speed() {
+        let slow = Container::new("Slowpoke", "kanto", 5, 90, 65, 65, 15, PokemonType::Water);
+        let fast = Container::new(
+            "Jolteon",
+            "kanto",
+            5,
+            65,
+            65,
+            60,
+            130,
+            PokemonType::Electric,
+        );
+        let tackle = attack(40, PokemonType::Normal);
+        let quick_attack = Move::new(
+            "Quick Attack",
+            40,
+            100,
+            30,
+            PokemonType::Normal,
+            MoveCategory::Physical,
+            "",
+            None,
+        );
+        assert_eq!(quick_attack.priority, 1);
+
+        let cases = [
+            (&slow, &tackle, &fast, &tackle, Side::Opponent),
+            (&fast, &tackle, &slow, &tackle, Side::Challenger),
+            (&slow, &quick_attack, &fast, &tackle, Side::Challenger),
+            (&fast, &tackle, &slow, &quick_attack, Side::Opponent),
+            (&slow, &quick_attack, &fast, &quick_attack, Side::Opponent),
+        ];
+        for (challenger, challenger_move, opponent, opponent_move, expected) in cases {
+            for tie_break in [true, false] {
+                assert_eq!(
+                    Battle::turn_order(
+                        challenger,
+                        challenger_move,
+                        opponent,
+                        opponent_move,
+                        tie_break
+                    ),
+                    expected
+                );
+            }
+        }
+
+        // Equal priority and speed: the tie break decides.
+        let twin = slow.clone();
+        assert_eq!(
+            Battle::turn_order(&slow, &tackle, &twin, &tackle, true),
+            Side::Challenger
+        );
+        assert_eq!(
+            Battle::turn_order(&slow, &tackle, &twin, &tackle, false),
+            Side::Opponent
+        );
+    }
+
+    #[test]
+    fn test_moves_without_pp_fall_back_to_struggle() {
+        let mut rng = Battle::seeded_rng(1);
+        let mut pikachu = pokemon(5, 55, 40, PokemonType::Electric);
+        assert_eq!(Battle::choose_action(&pikachu, &mut rng), Action::Struggle);
+
+        pikachu.learn_move(attack(40, PokemonType::Electric));
+        pikachu.learn_move(attack(40, PokemonType::Normal));
+        pikachu.moves[0].pp = 0;
+        for _ in 0..20 {
+            assert_eq!(Battle::choose_action(&pikachu, &mut rng), Action::Move(1));
+        }
+        pikachu.moves[1].pp = 0;
+        assert_eq!(Battle::choose_action(&pikachu, &mut rng), Action::Struggle);
+
+        // Struggle ignores types: it hits Ghosts and gets no STAB.
+        let chart = TypeEffectiveness::new();
+        let normal = pokemon(100, 50, 50, PokemonType::Normal);
+        let ghost = pokemon(50, 50, 50, PokemonType::Ghost);
+        let struggle =
+            Battle::calculate_damage(&normal, &ghost, &Move::struggle(), &chart, None, 1.0, false);
+        assert_eq!((struggle.amount, struggle.effectiveness), (44, 1.0));
+    }
+
+    #[test]
+    fn test_battle_spends_pp_and_survives_pokemon_without_moves() {
+        let mut challenger = pokemon(20, 60, 40, PokemonType::Fire);
+        let mut opponent = pokemon(20, 60, 40, PokemonType::Water);
+        challenger.learn_move(attack(40, PokemonType::Fire));
+        for pokemon in [&mut challenger, &mut opponent] {
+            pokemon.state = crate::container::ContainerState::Running;
+        }
+
+        let record = Battle::start_battle(
+            &mut challenger,
+            &mut opponent,
+            None,
+            &mut Battle::seeded_rng(7),
+            Some(&EvolutionManager::new()),
+            [&mut RandomAi, &mut RandomAi],
+        );
+        assert!(!challenger.is_active() || !opponent.is_active() || record.turns == MAX_TURNS);
+        assert!(opponent.stats.moves_used["Struggle"] > 0);
+        let used = challenger
+            .stats
+            .moves_used
+            .get("Test Move")
+            .copied()
+            .unwrap_or(0);
+        assert_eq!(challenger.moves[0].pp, 10 - used as u8);
+    }
+
+    #[test]
+    fn test_battle_records_its_events() {
+        let mut challenger = pokemon(50, 80, 40, PokemonType::Fire);
+        let mut opponent = pokemon(5, 20, 20, PokemonType::Grass);
+        challenger.learn_move(attack(90, PokemonType::Fire));
+        for pokemon in [&mut challenger, &mut opponent] {
+            pokemon.state = crate::container::ContainerState::Running;
+        }
+
+        let record = Battle::start_battle(
+            &mut challenger,
+            &mut opponent,
+            None,
+            &mut Battle::seeded_rng(7),
+            Some(&EvolutionManager::new()),
+            [&mut RandomAi, &mut RandomAi],
+        );
+        let events = &record.events;
+        assert!(matches!(events[0], BattleEvent::Start { .. }));
+        assert_eq!(events[1], BattleEvent::TurnStart { turn: 1 });
+        let turns = events
+            .iter()
+            .filter(|e| matches!(e, BattleEvent::TurnStart { .. }))
+            .count();
+        assert_eq!(turns as u32, record.turns);
+        let faints: Vec<_> = events
+            .iter()
+            .filter(|e| matches!(e, BattleEvent::Faint { .. }))
+            .collect();
+        assert_eq!(
+            faints,
+            [&BattleEvent::Faint {
+                side: Side::Opponent
+            }]
+        );
+        assert!(events.contains(&BattleEvent::End {
+            winner: Side::Challenger,
+            turns: record.turns
+        }));
+
+        // Beating a level 5 Pokémon is worth 150 EXP, over the 2500 a level 50
+        // Pokémon needs, so nobody levels up.
+        assert_eq!(
+            events.last(),
+            Some(&BattleEvent::ExpGained {
+                side: Side::Challenger,
+                amount: 150
+            })
+        );
+        assert_eq!((challenger.exp, challenger.level), (150, 50));
+        assert_eq!(opponent.exp, 0);
+        assert_eq!(record.levels_gained(), 0);
+
+        // The opponent has fainted, so the rematch is over before it starts.
+        let record = Battle::start_battle(
+            &mut challenger,
+            &mut opponent,
+            None,
+            &mut Battle::seeded_rng(7),
+            None,
+            [&mut RandomAi, &mut RandomAi],
+        );
+        assert_eq!(record.turns, 0);
+        assert_eq!(challenger.exp, 150);
+    }
+
+    #[test]
+    fn test_ais_pick_moves_and_can_forfeit() {
+        /// Uses the move at index 1 twice, then gives up.
+        struct GivesUp {
+            asked: u32,
+        }
+
+        impl BattleAi for GivesUp {
+            fn choose_action(
+                &mut self,
+                pokemon: &Container,
+                _opponent: &Container,
+                _view: &BattleView,
+                _rng: &mut dyn rand::RngCore,
+            ) -> Option<Action> {
+                self.asked += 1;
+                assert_eq!(pokemon.moves.len(), 2);
+                (self.asked < 3).then_some(Action::Move(1))
+            }
+        }
+
+        let mut challenger = pokemon(50, 50, 200, PokemonType::Normal);
+        let mut opponent = pokemon(50, 50, 200, PokemonType::Normal);
+        let mut growl = stat_move("Growl", Stat::Attack, -1);
+        growl.category = MoveCategory::Status;
+        challenger.learn_move(attack(40, PokemonType::Normal));
+        challenger.learn_move(growl);
+        opponent.learn_move(attack(40, PokemonType::Normal));
+        for pokemon in [&mut challenger, &mut opponent] {
+            pokemon.state = crate::container::ContainerState::Running;
+        }
+
+        let mut gives_up = GivesUp { asked: 0 };
+        let record = Battle::start_battle(
+            &mut challenger,
+            &mut opponent,
+            None,
+            &mut Battle::seeded_rng(7),
+            None,
+            [&mut gives_up, &mut GreedyAi],
+        );
+        assert_eq!(gives_up.asked, 3);
+        assert_eq!(record.turns, 3);
+        assert_eq!(
+            record.challenger_moves,
+            HashMap::from([("Growl".to_string(), 2)])
+        );
+        assert!(record.events.contains(&BattleEvent::Forfeit {
+            side: Side::Challenger
+        }));
+        assert!(record.events.contains(&BattleEvent::End {
+            winner: Side::Opponent,
+            turns: 3
+        }));
+        assert!(challenger.is_active() && opponent.is_active());
+        assert_eq!(opponent.stats.battles_won, 1);
+        assert_eq!(opponent.exp, 0);
+    }
+
+    #[test]
+    fn test_winner_evolves_when_it_levels_up() {
+        let evolutions = EvolutionManager::new();
+        let battle = |evolution_manager: Option<&EvolutionManager>| {
+            let mut pikachu = pokemon(24, 80, 40, PokemonType::Electric);
+            pikachu.name = "Pikachu".to_string();
+            pikachu.exp = pikachu.exp_to_next_level - 1;
+            pikachu.learn_move(attack(90, PokemonType::Electric));
+            let mut opponent = pokemon(5, 20, 20, PokemonType::Water);
+            for pokemon in [&mut pikachu, &mut opponent] {
+                pokemon.state = crate::container::ContainerState::Running;
+            }
+            let record = Battle::start_battle(
+                &mut pikachu,
+                &mut opponent,
+                None,
+                &mut Battle::seeded_rng(7),
+                evolution_manager,
+                [&mut RandomAi, &mut RandomAi],
+            );
+            (pikachu, record)
+        };
+
+        let (raichu, record) = battle(Some(&evolutions));
+        assert_eq!((raichu.name.as_str(), raichu.level), ("Raichu", 25));
+        assert_eq!(raichu.stats.evolution_count, 1);
+        assert_eq!(
+            record.events.last(),
+            Some(&BattleEvent::Evolved {
+                side: Side::Challenger,
+                from: "Pikachu".to_string(),
+                to: "Raichu".to_string()
+            })
+        );
+        // Level 25 stats are 83/41/52, times 1.5/1.3/1.4 for Raichu.
+        assert_eq!((raichu.attack, raichu.defense, raichu.speed), (125, 53, 73));
+        assert_eq!(raichu.hp, raichu.max_hp);
+
+        let (pikachu, _) = battle(None);
+        assert_eq!((pikachu.name.as_str(), pikachu.level), ("Pikachu", 25));
+        assert_eq!(pikachu.stats.evolution_count, 0);
+    }
+
+    #[test]
+    fn test_damage_scales_with_level_and_stats() {
+        let chart = TypeEffectiveness::new();
+        let tackle = attack(40, PokemonType::Normal);
+        let defender = pokemon(50, 50, 50, PokemonType::Water);
+
+        let low = pokemon(5, 50, 50, PokemonType::Fire);
+        let high = pokemon(100, 50, 50, PokemonType::Fire);
+        let strong = pokemon(100, 100, 50, PokemonType::Fire);
+        let damage = |attacker: &Container| {
+            Battle::calculate_damage(attacker, &defender, &tackle, &chart, None, 1.0, false).amount
+        };
+        // (2 × 5 / 5 + 2) × 40 / 50 + 2 = 5.2 and (2 × 100 / 5 + 2) × 40 / 50 + 2 = 35.6
+        assert_eq!(damage(&low), 5);
+        assert_eq!(damage(&high), 35);
+        assert!(damage(&strong) > damage(&high));
+
+        let min = Battle::calculate_damage(
+            &high,
+            &defender,
+            &tackle,
+            &chart,
+            None,
+            MIN_DAMAGE_ROLL,
+            false,
+        );
+        assert_eq!(min.amount, 30);
+    }
+
+    #[test]
+    fn test_damage_modifiers() {
+        let chart = TypeEffectiveness::new();
+        let attacker = pokemon(100, 50, 50, PokemonType::Water);
+        let grass = pokemon(50, 50, 50, PokemonType::Grass);
+        let fire = pokemon(50, 50, 50, PokemonType::Fire);
+        let ghost = pokemon(50, 50, 50, PokemonType::Ghost);
+        let damage = |battle_move: &Move, defender: &Container, critical: bool| {
+            Battle::calculate_damage(
+                &attacker,
+                defender,
+                battle_move,
+                &chart,
+                None,
+                1.0,
+                critical,
             )
-            .bright_green()
-            .bold()
+        };
+
+        let plain = damage(&attack(40, PokemonType::Normal), &grass, false);
+        assert_eq!((plain.amount, plain.effectiveness), (35, 1.0));
+        // STAB, and STAB on top of super effective.
+        assert_eq!(
+            damage(&attack(40, PokemonType::Water), &grass, false).amount,
+            26
         );
-        println!(
-            "{}",
-            "╚══════════════════════════════════════╝".bright_red()
+        assert_eq!(
+            damage(&attack(40, PokemonType::Water), &fire, false).amount,
+            106
         );
+        let critical = damage(&attack(40, PokemonType::Normal), &grass, true);
+        assert_eq!((critical.amount, critical.critical), (53, true));
+
+        let mut charizard = pokemon(50, 50, 50, PokemonType::Fire);
+        charizard.secondary_type = Some(PokemonType::Flying);
+        let rock = damage(&attack(40, PokemonType::Rock), &charizard, false);
+        assert_eq!((rock.amount, rock.effectiveness), (142, 4.0));
+        let ground = damage(&attack(40, PokemonType::Ground), &charizard, false);
+        assert_eq!(ground.amount, 0);
+
+        let immune = damage(&attack(40, PokemonType::Normal), &ghost, true);
+        assert_eq!((immune.amount, immune.critical), (0, false));
+        assert_eq!(
+            damage(&attack(0, PokemonType::Water), &fire, false).amount,
+            0
+        );
+    }
+
+    fn status_move(name: &str, power: u32, status: StatusCondition, chance: u8) -> Move {
+        Move::new(
+            name,
+            power,
+            100,
+            20,
+            PokemonType::Normal,
+            if power == 0 {
+                MoveCategory::Status
+            } else {
+                MoveCategory::Physical
+            },
+            "",
+            Some(MoveEffect {
+                effect_type: EffectType::StatusCondition(status),
+                chance,
+                turns: None,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_burn_halves_physical_damage() {
+        let chart = TypeEffectiveness::new();
+        let defender = pokemon(50, 50, 50, PokemonType::Water);
+        let mut attacker = pokemon(100, 50, 50, PokemonType::Fire);
+        let tackle = attack(40, PokemonType::Normal);
+        let mut special = attack(40, PokemonType::Normal);
+        special.category = MoveCategory::Special;
+        let damage = |attacker: &Container, battle_move: &Move| {
+            Battle::calculate_damage(attacker, &defender, battle_move, &chart, None, 1.0, false)
+                .amount
+        };
+
+        assert_eq!(damage(&attacker, &tackle), 35);
+        attacker.status_condition = Some(StatusCondition::Burn);
+        assert_eq!(damage(&attacker, &tackle), 17);
+        assert_eq!(damage(&attacker, &special), 35);
+    }
+
+    #[test]
+    fn test_paralysis_halves_speed_for_turn_order() {
+        let tackle = attack(40, PokemonType::Normal);
+        let slow = Container::new("Slowpoke", "kanto", 5, 90, 65, 65, 40, PokemonType::Water);
+        let mut fast = Container::new("Jolteon", "kanto", 5, 65, 65, 60, 70, PokemonType::Water);
+        assert_eq!(
+            Battle::turn_order(&fast, &tackle, &slow, &tackle, false),
+            Side::Challenger
+        );
+
+        fast.status_condition = Some(StatusCondition::Paralysis);
+        assert_eq!(Battle::effective_speed(&fast), 35);
+        assert_eq!(
+            Battle::turn_order(&fast, &tackle, &slow, &tackle, true),
+            Side::Opponent
+        );
+    }
+
+    #[test]
+    fn test_status_respects_immunities_and_existing_status() {
+        let thunder_wave = status_move("Thunder Wave", 0, StatusCondition::Paralysis, 100);
+        let toxic = status_move("Toxic", 0, StatusCondition::Poison, 100);
+        let water = pokemon(50, 50, 50, PokemonType::Water);
+        let electric = pokemon(50, 50, 50, PokemonType::Electric);
+        let mut skarmory = pokemon(50, 50, 50, PokemonType::Flying);
+        skarmory.secondary_type = Some(PokemonType::Steel);
+
+        assert_eq!(
+            Battle::status_to_inflict(&thunder_wave, &water),
+            Some(StatusCondition::Paralysis)
+        );
+        assert_eq!(Battle::status_to_inflict(&thunder_wave, &electric), None);
+        assert_eq!(Battle::status_to_inflict(&toxic, &skarmory), None);
+        assert_eq!(
+            Battle::status_to_inflict(&attack(40, PokemonType::Normal), &water),
+            None
+        );
+
+        let mut burned = water.clone();
+        burned.status_condition = Some(StatusCondition::Burn);
+        assert_eq!(Battle::status_to_inflict(&thunder_wave, &burned), None);
+    }
+
+    #[test]
+    fn test_battle_inflicts_status_and_chips_hp() {
+        let mut challenger = pokemon(50, 50, 50, PokemonType::Normal);
+        let mut opponent = pokemon(50, 50, 50, PokemonType::Water);
+        challenger.learn_move(status_move("Toxic", 0, StatusCondition::Poison, 100));
+        for pokemon in [&mut challenger, &mut opponent] {
+            pokemon.state = crate::container::ContainerState::Running;
+            pokemon.speed = 50;
+        }
+        challenger.speed = 100;
+
+        Battle::start_battle(
+            &mut challenger,
+            &mut opponent,
+            None,
+            &mut Battle::seeded_rng(7),
+            Some(&EvolutionManager::new()),
+            [&mut RandomAi, &mut RandomAi],
+        );
+        // Toxic goes first and never misses, so the opponent is poisoned from the
+        // first turn. Toxic does no damage itself: everything beyond Struggle's
+        // recoil is poison.
+        assert!(challenger.stats.moves_used["Toxic"] > 0);
+        assert_eq!(challenger.stats.total_damage_dealt, 0);
+        let recoil = opponent.stats.moves_used["Struggle"] * 25;
+        assert!(opponent.stats.total_damage_taken > recoil);
+        if opponent.hp == 0 {
+            assert_eq!(opponent.status_condition, None);
+        } else {
+            assert_eq!(opponent.status_condition, Some(StatusCondition::Poison));
+        }
+    }
+
+    fn stat_move(name: &str, stat: Stat, stages: i8) -> Move {
+        Move::new(
+            name,
+            0,
+            100,
+            20,
+            PokemonType::Normal,
+            MoveCategory::Status,
+            "",
+            Some(MoveEffect {
+                effect_type: EffectType::StatChange { stat, stages },
+                chance: 100,
+                turns: None,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_stat_stages_scale_damage_speed_and_accuracy() {
+        let chart = TypeEffectiveness::new();
+        let tackle = attack(40, PokemonType::Normal);
+        let mut attacker = pokemon(100, 50, 50, PokemonType::Water);
+        let mut defender = pokemon(50, 50, 50, PokemonType::Grass);
+        let damage = |attacker: &Container, defender: &Container, critical: bool| {
+            Battle::calculate_damage(attacker, defender, &tackle, &chart, None, 1.0, critical)
+                .amount
+        };
+
+        // (2 × 100 / 5 + 2) × 40 × 2 / 50 + 2 = 69.2
+        attacker.stat_stages.change(Stat::Attack, 2);
+        assert_eq!(damage(&attacker, &defender, false), 69);
+        // Special Attack doesn't help a physical move.
+        let mut special = pokemon(100, 50, 50, PokemonType::Water);
+        special.stat_stages.change(Stat::SpecialAttack, 2);
+        assert_eq!(damage(&special, &defender, false), 35);
+
+        // A critical hit ignores the attacker's drops and the defender's boosts.
+        attacker.stat_stages = StatStages::default();
+        attacker.stat_stages.change(Stat::Attack, -2);
+        defender.stat_stages.change(Stat::Defense, 2);
+        assert_eq!(damage(&attacker, &defender, false), 10);
+        assert_eq!(damage(&attacker, &defender, true), 53);
+
+        attacker.stat_stages.change(Stat::Speed, 1);
+        assert_eq!(Battle::effective_speed(&attacker), 75);
+
+        let mut sand_attack = attack(40, PokemonType::Normal);
+        sand_attack.accuracy = 90;
+        assert_eq!(Battle::hit_chance(&attacker, &defender, &sand_attack), 0.9);
+        defender.stat_stages.change(Stat::Evasion, 1);
+        assert!((Battle::hit_chance(&attacker, &defender, &sand_attack) - 0.675).abs() < 1e-6);
+        attacker.stat_stages.change(Stat::Accuracy, 3);
+        assert_eq!(Battle::hit_chance(&attacker, &defender, &sand_attack), 1.0);
+    }
+
+    #[test]
+    fn test_stat_moves_target_the_right_side_and_reset_after_battle() {
+        let mut challenger = pokemon(50, 50, 50, PokemonType::Normal);
+        let mut opponent = pokemon(50, 50, 50, PokemonType::Water);
+        let mut rng = Battle::seeded_rng(1);
+        let mut log = BattleLog::new();
+        let chart = TypeEffectiveness::new();
+        challenger.learn_move(stat_move("Swords Dance", Stat::Attack, 2));
+        challenger.learn_move(stat_move("Growl", Stat::Attack, -1));
+        {
+            let mut user = Fighter::new(&mut challenger, Side::Challenger, &mut rng);
+            let mut target = Fighter::new(&mut opponent, Side::Opponent, &mut rng);
+            Battle::execute_action(
+                &mut user,
+                &mut target,
+                Action::Move(0),
+                &chart,
+                &mut None,
+                &mut log,
+                &mut rng,
+            );
+            Battle::execute_action(
+                &mut user,
+                &mut target,
+                Action::Move(1),
+                &chart,
+                &mut None,
+                &mut log,
+                &mut rng,
+            );
+        }
+        assert_eq!(challenger.stat_stages.attack, 2);
+        assert_eq!(opponent.stat_stages.attack, -1);
+
+        for pokemon in [&mut challenger, &mut opponent] {
+            pokemon.state = crate::container::ContainerState::Running;
+        }
+        Battle::start_battle(
+            &mut challenger,
+            &mut opponent,
+            None,
+            &mut Battle::seeded_rng(7),
+            Some(&EvolutionManager::new()),
+            [&mut RandomAi, &mut RandomAi],
+        );
+        assert_eq!(challenger.stat_stages, StatStages::default());
+        assert_eq!(opponent.stat_stages, StatStages::default());
+    }
+
+    #[test]
+    fn test_status_moves_ignore_type_immunities() {
+        let mut challenger = pokemon(50, 50, 50, PokemonType::Normal);
+        let mut opponent = pokemon(50, 50, 50, PokemonType::Ghost);
+        let mut rng = Battle::seeded_rng(1);
+        let mut log = BattleLog::new();
+        let chart = TypeEffectiveness::new();
+        challenger.learn_move(stat_move("Swords Dance", Stat::Attack, 2));
+        challenger.learn_move(stat_move("Growl", Stat::Attack, -1));
+        challenger.learn_move(attack(40, PokemonType::Normal));
+        {
+            let mut user = Fighter::new(&mut challenger, Side::Challenger, &mut rng);
+            let mut target = Fighter::new(&mut opponent, Side::Opponent, &mut rng);
+            for index in 0..3 {
+                Battle::execute_action(
+                    &mut user,
+                    &mut target,
+                    Action::Move(index),
+                    &chart,
+                    &mut None,
+                    &mut log,
+                    &mut rng,
+                );
+            }
+        }
+        assert_eq!(challenger.stat_stages.attack, 2);
+        assert_eq!(opponent.stat_stages.attack, -1);
+        // Only the Normal attack is stopped by the Ghost type.
+        let no_effect = log
+            .into_events()
+            .into_iter()
+            .filter(|event| matches!(event, BattleEvent::NoEffect { .. }))
+            .count();
+        assert_eq!(no_effect, 1);
+        assert_eq!(opponent.hp, opponent.max_hp);
+    }
+
+    #[test]
+    fn test_weather_changes_damage_and_chips_hp() {
+        let chart = TypeEffectiveness::new();
+        let attacker = pokemon(100, 50, 50, PokemonType::Normal);
+        let defender = pokemon(50, 50, 50, PokemonType::Normal);
+        let damage = |battle_move: &Move, weather: Option<Weather>| {
+            Battle::calculate_damage(
+                &attacker,
+                &defender,
+                battle_move,
+                &chart,
+                weather,
+                1.0,
+                false,
+            )
+            .amount
+        };
+        let ember = attack(40, PokemonType::Fire);
+        let bubble = attack(40, PokemonType::Water);
+        assert_eq!(damage(&ember, None), 35);
+        assert_eq!(damage(&ember, Some(Weather::Sunny)), 53);
+        assert_eq!(damage(&ember, Some(Weather::Rain)), 17);
+        assert_eq!(damage(&bubble, Some(Weather::Rain)), 53);
+        assert_eq!(damage(&bubble, Some(Weather::Sandstorm)), 35);
+        assert_eq!(damage(&Move::struggle(), Some(Weather::Sunny)), 44);
+
+        let mut rng = Battle::seeded_rng(1);
+        let mut log = BattleLog::new();
+        let mut normal = pokemon(50, 50, 50, PokemonType::Normal);
+        let mut onix = pokemon(50, 50, 50, PokemonType::Rock);
+        {
+            let mut normal = Fighter::new(&mut normal, Side::Challenger, &mut rng);
+            let mut onix = Fighter::new(&mut onix, Side::Opponent, &mut rng);
+            Battle::apply_weather_damage(&mut normal, Weather::Sandstorm, &mut log);
+            Battle::apply_weather_damage(&mut onix, Weather::Sandstorm, &mut log);
+            Battle::apply_weather_damage(&mut onix, Weather::Rain, &mut log);
+            assert_eq!((normal.pokemon.hp, onix.pokemon.hp), (94, 100));
+            Battle::apply_weather_damage(&mut onix, Weather::Hail, &mut log);
+        }
+        assert_eq!(onix.hp, 94);
+        assert_eq!(
+            log.into_events(),
+            vec![
+                BattleEvent::WeatherDamage {
+                    side: Side::Challenger,
+                    weather: Weather::Sandstorm,
+                    amount: 6,
+                    hp: 94
+                },
+                BattleEvent::WeatherDamage {
+                    side: Side::Opponent,
+                    weather: Weather::Hail,
+                    amount: 6,
+                    hp: 94
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weather_from_moves_runs_out() {
+        let mut rng = Battle::seeded_rng(1);
+        let mut log = BattleLog::new();
+        let chart = TypeEffectiveness::new();
+        let mut challenger = pokemon(50, 50, 50, PokemonType::Water);
+        let mut opponent = pokemon(50, 50, 50, PokemonType::Normal);
+        challenger.learn_move(Move::new(
+            "Rain Dance",
+            0,
+            100,
+            5,
+            PokemonType::Water,
+            MoveCategory::Status,
+            "",
+            Some(MoveEffect {
+                effect_type: EffectType::WeatherChange(Weather::Rain),
+                chance: 100,
+                turns: Some(2),
+            }),
+        ));
+
+        let mut weather = None;
+        let mut user = Fighter::new(&mut challenger, Side::Challenger, &mut rng);
+        let mut target = Fighter::new(&mut opponent, Side::Opponent, &mut rng);
+        Battle::execute_action(
+            &mut user,
+            &mut target,
+            Action::Move(0),
+            &chart,
+            &mut weather,
+            &mut log,
+            &mut rng,
+        );
+        assert_eq!(
+            weather,
+            Some(ActiveWeather {
+                weather: Weather::Rain,
+                turns_left: Some(2)
+            })
+        );
+        Battle::tick_weather(&mut weather, &mut log);
+        assert!(weather.is_some());
+        Battle::tick_weather(&mut weather, &mut log);
+        assert_eq!(weather, None);
+
+        // Weather chosen for the whole battle never runs out.
+        let mut preset = Some(ActiveWeather {
+            weather: Weather::Hail,
+            turns_left: None,
+        });
+        Battle::tick_weather(&mut preset, &mut log);
+        assert!(preset.is_some());
+        assert_eq!("rain".parse::<Weather>(), Ok(Weather::Rain));
+        assert_eq!("Sun".parse::<Weather>(), Ok(Weather::Sunny));
+        assert!("fog".parse::<Weather>().is_err());
     }
 
-    fn execute_move(attacker: &mut Container, defender: &mut Container, move_index: usize) {
-        if let Some(battle_move) = attacker.moves.get(move_index) {
-            let count = attacker
-                .stats
-                .moves_used
-                .entry(battle_move.name.clone())
-                .or_insert(0);
-            *count += 1;
+    #[test]
+    fn test_special_moves_use_special_stats() {
+        let chart = TypeEffectiveness::new();
+        let mut attacker = pokemon(100, 50, 50, PokemonType::Normal);
+        let mut defender = pokemon(50, 50, 50, PokemonType::Water);
+        attacker.sp_attack = 100;
+        defender.defense = 25;
+        let tackle = attack(40, PokemonType::Normal);
+        let mut swift = attack(40, PokemonType::Normal);
+        swift.category = MoveCategory::Special;
+        let damage = |defender: &Container, battle_move: &Move| {
+            Battle::calculate_damage(&attacker, defender, battle_move, &chart, None, 1.0, false)
+                .amount
+        };
 
-            println!("💫 {} uses {}!", attacker.name, battle_move.name);
-            let damage = battle_move.power;
-            defender.hp -= damage as i32;
+        // Both double the attack-to-defense ratio, each through its own stats:
+        // (2 × 100 / 5 + 2) × 40 × 2 / 50 + 2 = 69.2, then × 1.5 STAB.
+        assert_eq!(damage(&defender, &tackle), 103);
+        assert_eq!(damage(&defender, &swift), 103);
+        defender.sp_defense = 100;
+        assert_eq!(damage(&defender, &swift), 53);
 
-            attacker.stats.total_damage_dealt += damage;
-            defender.stats.total_damage_taken += damage;
+        let mut status = attack(40, PokemonType::Normal);
+        status.category = MoveCategory::Status;
+        assert_eq!(damage(&defender, &status), 0);
+    }
 
-            println!("💥 {} takes {} damage!", defender.name, damage);
+    #[test]
+    fn test_same_seed_replays_the_same_battle() {
+        let mut challenger = pokemon(30, 60, 50, PokemonType::Fire);
+        let mut opponent = pokemon(30, 55, 60, PokemonType::Grass);
+        challenger.learn_move(attack(60, PokemonType::Fire));
+        challenger.learn_move(status_move("Will-O-Wisp", 0, StatusCondition::Burn, 100));
+        opponent.learn_move(attack(50, PokemonType::Grass));
+        opponent.learn_move(stat_move("Growl", Stat::Attack, -1));
+        for pokemon in [&mut challenger, &mut opponent] {
+            pokemon.state = crate::container::ContainerState::Running;
         }
+
+        let battle = |seed: u64| {
+            let (mut challenger, mut opponent) = (challenger.clone(), opponent.clone());
+            let record = Battle::start_battle(
+                &mut challenger,
+                &mut opponent,
+                Some(Weather::Sunny),
+                &mut Battle::seeded_rng(seed),
+                Some(&EvolutionManager::new()),
+                [&mut RandomAi, &mut RandomAi],
+            );
+            (
+                record.turns,
+                record.winner,
+                record.challenger_damage,
+                record.opponent_damage,
+                record.challenger_moves,
+                record.opponent_moves,
+                challenger.hp,
+                opponent.hp,
+                opponent.status_condition,
+            )
+        };
+        assert_eq!(battle(42), battle(42));
+        let outcomes: Vec<_> = (0..10).map(battle).collect();
+        assert!(outcomes.iter().any(|outcome| *outcome != outcomes[0]));
     }
 }
@@ -0,0 +1,225 @@
+use crate::container::Container;
+use crate::moves::{Move, PokemonType, StatusCondition};
+use crate::stats::PokemonStats;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::UNIX_EPOCH;
+use std::{fmt, str::FromStr};
+
+/// Bumped whenever the bundle layout changes in a way older builds can't read.
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamespaceBundle {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub namespace: String,
+    pub containers: Vec<ContainerRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerRecord {
+    pub name: String,
+    pub level: u32,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub attack: u32,
+    pub defense: u32,
+    /// Missing from bundles written before special stats existed; defaults to
+    /// attack and defense.
+    #[serde(default)]
+    pub sp_attack: Option<u32>,
+    #[serde(default)]
+    pub sp_defense: Option<u32>,
+    pub speed: u32,
+    pub pokemon_type: PokemonType,
+    #[serde(default)]
+    pub secondary_type: Option<PokemonType>,
+    #[serde(default)]
+    pub status_condition: Option<StatusCondition>,
+    pub exp: u32,
+    pub exp_to_next_level: u32,
+    pub moves: Vec<Move>,
+    pub labels: HashMap<String, String>,
+    pub stats: PokemonStats,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    Skip,
+    Rename,
+    Overwrite,
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictPolicy::Skip => write!(f, "skip"),
+            ConflictPolicy::Rename => write!(f, "rename"),
+            ConflictPolicy::Overwrite => write!(f, "overwrite"),
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(ConflictPolicy::Skip),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            _ => Err(format!(
+                "Invalid conflict policy: {} (expected skip, rename or overwrite)",
+                s
+            )),
+        }
+    }
+}
+
+impl NamespaceBundle {
+    pub fn new(namespace: &str, containers: &[Container]) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            exported_at: Utc::now(),
+            namespace: namespace.to_string(),
+            containers: containers.iter().map(ContainerRecord::from).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let bundle: Self =
+            serde_json::from_str(json).map_err(|e| format!("invalid bundle: {}", e))?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(format!(
+                "bundle version {} is newer than the supported version {}",
+                bundle.version, BUNDLE_VERSION
+            ));
+        }
+        Ok(bundle)
+    }
+
+    /// Rebuilds the bundled containers as fresh, not-yet-started containers in `namespace`.
+    pub fn to_containers(&self, namespace: &str) -> Vec<Container> {
+        self.containers
+            .iter()
+            .map(|record| record.to_container(namespace))
+            .collect()
+    }
+}
+
+impl From<&Container> for ContainerRecord {
+    fn from(container: &Container) -> Self {
+        Self {
+            name: container.name.clone(),
+            level: container.level,
+            hp: container.hp,
+            max_hp: container.max_hp,
+            attack: container.attack,
+            defense: container.defense,
+            speed: container.speed,
+            pokemon_type: container.pokemon_type,
+            secondary_type: container.secondary_type,
+            sp_attack: Some(container.sp_attack),
+            sp_defense: Some(container.sp_defense),
+            status_condition: container.status_condition,
+            exp: container.exp,
+            exp_to_next_level: container.exp_to_next_level,
+            moves: container.moves.clone(),
+            labels: container.labels.clone(),
+            stats: container.stats.clone(),
+            created_at: DateTime::<Utc>::from(container.created_at),
+        }
+    }
+}
+
+impl ContainerRecord {
+    fn to_container(&self, namespace: &str) -> Container {
+        let mut container = Container::new(
+            &self.name,
+            namespace,
+            self.level,
+            self.max_hp,
+            self.attack,
+            self.defense,
+            self.speed,
+            self.pokemon_type,
+        );
+        container.secondary_type = self.secondary_type;
+        container.sp_attack = self.sp_attack.unwrap_or(self.attack);
+        container.sp_defense = self.sp_defense.unwrap_or(self.defense);
+        container.status_condition = self.status_condition;
+        container.hp = self.hp.min(self.max_hp);
+        container.exp = self.exp;
+        container.exp_to_next_level = self.exp_to_next_level;
+        container.stats = self.stats.clone();
+        container.labels.extend(self.labels.clone());
+        container
+            .labels
+            .insert("namespace".to_string(), namespace.to_string());
+        container.created_at =
+            UNIX_EPOCH + std::time::Duration::from_secs(self.created_at.timestamp().max(0) as u64);
+        for mv in &self.moves {
+            container.learn_move(mv.clone());
+        }
+        container
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::MoveCategory;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let mut pikachu = Container::new(
+            "Pikachu",
+            "kanto",
+            12,
+            45,
+            55,
+            40,
+            90,
+            PokemonType::Electric,
+        );
+        pikachu.hp = 30;
+        pikachu.learn_move(Move::new(
+            "Thunder Shock",
+            40,
+            100,
+            30,
+            PokemonType::Electric,
+            MoveCategory::Special,
+            "A jolt of electricity",
+            None,
+        ));
+        pikachu.labels.insert("team".to_string(), "red".to_string());
+
+        let json = NamespaceBundle::new("kanto", &[pikachu]).to_json().unwrap();
+        let bundle = NamespaceBundle::from_json(&json).unwrap();
+        let restored = bundle.to_containers("johto");
+
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].hp, 30);
+        assert_eq!(restored[0].max_hp, 45);
+        assert_eq!(restored[0].moves[0].name, "Thunder Shock");
+        assert_eq!(restored[0].namespace, "johto");
+        assert_eq!(restored[0].labels["namespace"], "johto");
+        assert_eq!(restored[0].labels["team"], "red");
+    }
+
+    #[test]
+    fn test_rejects_newer_bundle_version() {
+        let mut bundle = NamespaceBundle::new("kanto", &[]);
+        bundle.version = BUNDLE_VERSION + 1;
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(NamespaceBundle::from_json(&json).is_err());
+    }
+}
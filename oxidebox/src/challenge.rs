@@ -1,9 +1,11 @@
-use chrono::{DateTime, Utc};
+use crate::storage::{self, Storage};
+use chrono::{DateTime, Duration, Utc};
 use colored::*;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ChallengeType {
     BattleWin,
     CatchPokemon,
@@ -11,6 +13,20 @@ pub enum ChallengeType {
     UseMoves,
 }
 
+impl FromStr for ChallengeType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "BattleWin" => Ok(ChallengeType::BattleWin),
+            "CatchPokemon" => Ok(ChallengeType::CatchPokemon),
+            "LevelUp" => Ok(ChallengeType::LevelUp),
+            "UseMoves" => Ok(ChallengeType::UseMoves),
+            _ => Err(format!("Invalid challenge type: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Challenge {
     pub id: String,
@@ -21,15 +37,26 @@ pub struct Challenge {
     pub reward_exp: u32,
     pub reward_items: Vec<String>,
     pub completed: bool,
+    pub claimed: bool,
     pub created_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
 }
 
+/// Result of `Storage::claim_challenge`.
+#[derive(Debug)]
+pub enum ClaimOutcome {
+    Claimed(Challenge),
+    NotCompleted,
+    AlreadyClaimed,
+    NotFound,
+}
+
 #[derive(Debug)]
 pub struct ChallengeManager {
     pub active_challenges: HashMap<String, Challenge>,
     pub completed_challenges: Vec<Challenge>,
     pub daily_reset_time: DateTime<Utc>,
+    dirty: bool,
 }
 
 impl ChallengeManager {
@@ -38,16 +65,50 @@ impl ChallengeManager {
             active_challenges: HashMap::new(),
             completed_challenges: Vec::new(),
             daily_reset_time: Utc::now(),
+            dirty: false,
+        }
+    }
+
+    /// Loads today's challenges from storage, generating (and storing) a new set
+    /// only when the previous set has expired.
+    pub fn load(storage: &mut dyn Storage) -> storage::Result<Self> {
+        let mut manager = Self::new();
+        let challenges = storage.load_challenges(Utc::now())?;
+        if let Some(expires_at) = challenges.iter().map(|c| c.expires_at).min() {
+            manager.daily_reset_time = expires_at;
+        }
+        for challenge in challenges {
+            manager
+                .active_challenges
+                .insert(challenge.id.clone(), challenge);
+        }
+        manager.check_daily_reset();
+        manager.save(storage)?;
+        Ok(manager)
+    }
+
+    /// Writes progress back to storage if anything changed since the last save.
+    pub fn save(&mut self, storage: &mut dyn Storage) -> storage::Result<()> {
+        if self.dirty {
+            let challenges: Vec<Challenge> = self.active_challenges.values().cloned().collect();
+            storage.save_challenges(&challenges)?;
+            self.dirty = false;
         }
+        Ok(())
     }
 
-    pub fn generate_daily_challenges(&mut self) {
+    /// Challenge ids are derived from the day they belong to, so the same id is
+    /// printed by every command until the next reset at midnight UTC.
+    fn generate_challenges_for(&mut self, now: DateTime<Utc>) {
         self.active_challenges.clear();
+        self.dirty = true;
+        let day = now.format("%Y%m%d");
+        let expires_at = Self::next_reset(now);
         
-        // Generate 3 random daily challenges
+        // Generate 4 random daily challenges
         let challenges = vec![
             Challenge {
-                id: format!("daily-{}", Utc::now().timestamp()),
+                id: format!("daily-{}-1", day),
                 challenge_type: ChallengeType::BattleWin,
                 description: "Win 3 battles today".to_string(),
                 target: 3,
@@ -55,11 +116,12 @@ impl ChallengeManager {
                 reward_exp: 500,
                 reward_items: vec!["Rare Candy".to_string()],
                 completed: false,
-                created_at: Utc::now(),
-                expires_at: Utc::now() + chrono::Duration::days(1),
+                claimed: false,
+                created_at: now,
+                expires_at,
             },
             Challenge {
-                id: format!("daily-{}", Utc::now().timestamp() + 1),
+                id: format!("daily-{}-2", day),
                 challenge_type: ChallengeType::CatchPokemon,
                 description: "Catch 2 Pokémon today".to_string(),
                 target: 2,
@@ -67,11 +129,12 @@ impl ChallengeManager {
                 reward_exp: 300,
                 reward_items: vec!["Poké Ball".to_string(), "Great Ball".to_string()],
                 completed: false,
-                created_at: Utc::now(),
-                expires_at: Utc::now() + chrono::Duration::days(1),
+                claimed: false,
+                created_at: now,
+                expires_at,
             },
             Challenge {
-                id: format!("daily-{}", Utc::now().timestamp() + 2),
+                id: format!("daily-{}-3", day),
                 challenge_type: ChallengeType::UseMoves,
                 description: "Use 10 moves in battles".to_string(),
                 target: 10,
@@ -79,19 +142,42 @@ impl ChallengeManager {
                 reward_exp: 200,
                 reward_items: vec!["PP Up".to_string()],
                 completed: false,
-                created_at: Utc::now(),
-                expires_at: Utc::now() + chrono::Duration::days(1),
+                claimed: false,
+                created_at: now,
+                expires_at,
+            },
+            Challenge {
+                id: format!("daily-{}-4", day),
+                challenge_type: ChallengeType::LevelUp,
+                description: "Level up a Pokémon in battle".to_string(),
+                target: 1,
+                progress: 0,
+                reward_exp: 400,
+                reward_items: vec!["Exp. Share".to_string()],
+                completed: false,
+                claimed: false,
+                created_at: now,
+                expires_at,
             },
         ];
 
         for challenge in challenges {
             self.active_challenges.insert(challenge.id.clone(), challenge);
         }
+        self.daily_reset_time = expires_at;
+    }
+
+    fn next_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+        (now.date_naive() + Duration::days(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
     }
 
     pub fn update_challenge_progress(&mut self, challenge_type: ChallengeType, amount: u32) {
         for (_, challenge) in self.active_challenges.iter_mut() {
             if challenge.challenge_type == challenge_type && !challenge.completed {
+                self.dirty = true;
                 challenge.progress += amount;
                 if challenge.progress >= challenge.target {
                     challenge.completed = true;
@@ -108,25 +194,75 @@ impl ChallengeManager {
 
     pub fn display_challenges(&self) {
         println!("{}", "=== Daily Challenges ===".bright_cyan());
-        for (_, challenge) in &self.active_challenges {
-            let progress_bar = format!("[{}{}]",
-                "=".repeat((challenge.progress as f32 / challenge.target as f32 * 20.0) as usize),
-                " ".repeat(20 - (challenge.progress as f32 / challenge.target as f32 * 20.0) as usize)
-            );
-            println!("{}", format!("{} ({}/{})", challenge.description, challenge.progress, challenge.target).bright_white());
+        let remaining = self.daily_reset_time - Utc::now();
+        println!(
+            "{}",
+            format!(
+                "Resets in {}h {}m",
+                remaining.num_hours().max(0),
+                (remaining.num_minutes() % 60).max(0)
+            )
+            .bright_white()
+        );
+        let mut challenges: Vec<&Challenge> = self.active_challenges.values().collect();
+        challenges.sort_by(|a, b| a.id.cmp(&b.id));
+        for challenge in challenges {
+            let filled = (challenge.progress.min(challenge.target) as f32 / challenge.target as f32
+                * 20.0) as usize;
+            let progress_bar = format!("[{}{}]", "=".repeat(filled), " ".repeat(20 - filled));
+            println!("{}", format!("[{}] {} ({}/{})", challenge.id, challenge.description, challenge.progress, challenge.target).bright_white());
             println!("{}", progress_bar.bright_green());
             println!("{}", format!("Rewards: {} EXP, {}", 
                 challenge.reward_exp,
                 challenge.reward_items.join(", ")
             ).bright_yellow());
+            if challenge.claimed {
+                println!("{}", "✅ Reward claimed".bright_green());
+            } else if challenge.completed {
+                println!(
+                    "{}",
+                    format!("🎁 Claim with: claim-reward {}", challenge.id).bright_green()
+                );
+            }
             println!("{}", "-------------------".bright_cyan());
         }
     }
 
-    pub fn check_daily_reset(&mut self) {
-        if Utc::now() > self.daily_reset_time {
-            self.generate_daily_challenges();
-            self.daily_reset_time = Utc::now() + chrono::Duration::days(1);
+    /// Regenerates the challenges once the reset boundary has passed.
+    pub fn check_daily_reset(&mut self) -> bool {
+        self.check_daily_reset_at(Utc::now())
+    }
+
+    fn check_daily_reset_at(&mut self, now: DateTime<Utc>) -> bool {
+        if self.active_challenges.is_empty() || now >= self.daily_reset_time {
+            self.generate_challenges_for(now);
+            true
+        } else {
+            false
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_challenges_only_reset_at_midnight() {
+        let morning = Utc.with_ymd_and_hms(2026, 10, 19, 8, 30, 0).unwrap();
+        let mut manager = ChallengeManager::new();
+        assert!(manager.check_daily_reset_at(morning));
+        assert!(manager.active_challenges.contains_key("daily-20261019-1"));
+
+        manager.update_challenge_progress(ChallengeType::CatchPokemon, 1);
+        let evening = Utc.with_ymd_and_hms(2026, 10, 19, 23, 59, 0).unwrap();
+        assert!(!manager.check_daily_reset_at(evening));
+        assert_eq!(manager.active_challenges["daily-20261019-2"].progress, 1);
+
+        let next_day = Utc.with_ymd_and_hms(2026, 10, 20, 0, 0, 0).unwrap();
+        assert!(manager.check_daily_reset_at(next_day));
+        assert!(manager.active_challenges.contains_key("daily-20261020-2"));
+        assert_eq!(manager.active_challenges["daily-20261020-2"].progress, 0);
+    }
+}
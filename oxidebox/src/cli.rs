@@ -1,9 +1,15 @@
+use crate::ai::AiStrategy;
+use crate::bundle::ConflictPolicy;
+use crate::moves::{PokemonType, Weather};
+use crate::replay::{ReplayFormat, ReplaySpeed};
 use clap::{Parser, Subcommand};
-use crate::moves::PokemonType;
 
 #[derive(Parser)]
 #[clap(author, version, about)]
 pub struct Cli {
+    /// Act as this trainer instead of the current one (see `trainer switch`)
+    #[clap(long, global = true, value_name = "NAME")]
+    pub trainer: Option<String>,
     #[clap(subcommand)]
     pub command: Commands,
 }
@@ -25,6 +31,15 @@ pub enum Commands {
         defense: u16,
         speed: u16,
         pokemon_type: PokemonType,
+        /// Give the Pokémon a second type
+        #[clap(long, value_name = "TYPE")]
+        secondary_type: Option<PokemonType>,
+        /// Special attack, used by special moves (defaults to attack)
+        #[clap(long)]
+        sp_attack: Option<u16>,
+        /// Special defense, used against special moves (defaults to defense)
+        #[clap(long)]
+        sp_defense: Option<u16>,
     },
     Start {
         id: String,
@@ -35,8 +50,21 @@ pub enum Commands {
     Pause {
         id: String,
     },
+    /// Delete a Pokémon for good
+    Release {
+        id: String,
+    },
     List {
         namespace: Option<String>,
+        /// Only show Pokémon of this type
+        #[clap(long = "type", value_name = "TYPE")]
+        pokemon_type: Option<PokemonType>,
+        /// Only show Pokémon at or above this level
+        #[clap(long)]
+        min_level: Option<u32>,
+        /// Show at most N Pokémon
+        #[clap(long, value_name = "N")]
+        limit: Option<usize>,
     },
     Status {
         id: String,
@@ -44,6 +72,24 @@ pub enum Commands {
     Battle {
         id1: String,
         id2: String,
+        /// Battle in this weather from the start: sunny, rain, sandstorm or hail
+        #[clap(long)]
+        weather: Option<Weather>,
+        /// Seed the battle's random numbers to replay an earlier battle
+        #[clap(long, value_name = "N")]
+        seed: Option<u64>,
+        /// Don't let a Pokemon that levels up evolve
+        #[clap(long)]
+        no_evolve: bool,
+        /// Pick the first Pokemon's move yourself each turn
+        #[clap(long)]
+        interactive: bool,
+        /// How the first Pokemon picks its moves: random, greedy, defensive or lookahead
+        #[clap(long, default_value = "random", conflicts_with = "interactive")]
+        ai1: AiStrategy,
+        /// How the second Pokemon picks its moves
+        #[clap(long, default_value = "random")]
+        ai2: AiStrategy,
     },
     Save {
         id: String,
@@ -52,8 +98,151 @@ pub enum Commands {
         id: String,
     },
     Stats,
+    /// Show a stored battle again, turn by turn
+    Replay {
+        battle_id: String,
+        /// Playback speed, like 2x or 0.5x
+        #[clap(long, default_value = "1x")]
+        speed: ReplaySpeed,
+        /// text, or json for the raw event stream
+        #[clap(long, default_value = "text")]
+        format: ReplayFormat,
+    },
     Challenges,
     ClaimReward {
         challenge_id: String,
     },
+    /// Write a backup of the database, or configure automatic backups with --auto
+    Backup {
+        file: Option<String>,
+        /// Keep N rotating backups, taken before every destructive command (0 disables)
+        #[clap(long, value_name = "N")]
+        auto: Option<usize>,
+    },
+    /// Replace the database with a backup, keeping the current one as pokemon.db.rollback
+    Restore {
+        file: String,
+    },
+    /// Write a namespace with its containers, moves, labels and stats to a JSON bundle
+    Export {
+        namespace: String,
+        /// Output file (defaults to stdout)
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+    /// Load a namespace bundle produced by `export`
+    Import {
+        file: String,
+        /// Import into this namespace instead of the one recorded in the bundle
+        #[clap(long, value_name = "NS")]
+        into: Option<String>,
+        /// What to do when a Pokémon with the same name exists: skip, rename or overwrite
+        #[clap(long, default_value = "skip")]
+        on_conflict: ConflictPolicy,
+    },
+    /// Build teams of up to six Pokémon
+    Team {
+        #[clap(subcommand)]
+        command: TeamCommands,
+    },
+    /// Manage trainer profiles, each with their own namespaces and progression
+    Trainer {
+        #[clap(subcommand)]
+        command: TrainerCommands,
+    },
+    /// Roll back the last N commands that changed data
+    Undo {
+        #[clap(default_value = "1")]
+        count: usize,
+    },
+    /// Show the recent commands that `undo` can roll back
+    History {
+        /// Show at most N commands
+        #[clap(long, value_name = "N", default_value = "10")]
+        limit: usize,
+    },
+    /// Check the database for inconsistencies such as orphaned moves or unknown types
+    Doctor {
+        /// Repair the problems found
+        #[clap(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TeamCommands {
+    /// Create an empty team
+    Create { name: String },
+    /// Add a Pokémon to the end of a team
+    Add {
+        team: String,
+        id: String,
+        /// Put the Pokémon first, making it the team's lead
+        #[clap(long)]
+        lead: bool,
+    },
+    /// Remove a Pokémon from a team
+    Remove { team: String, id: String },
+    /// List all teams
+    Ls,
+    /// Show a team's members, lead first
+    Show { team: String },
+    /// Delete a team (its Pokémon are kept)
+    Rm { team: String },
+}
+
+#[derive(Subcommand)]
+pub enum TrainerCommands {
+    /// Create a new trainer profile
+    Create { name: String },
+    /// List trainer profiles
+    Ls,
+    /// Make a trainer the current one for later commands
+    Switch { name: String },
+}
+
+impl Commands {
+    /// Commands that modify or drop existing data and trigger an automatic backup first.
+    pub fn is_destructive(&self) -> bool {
+        matches!(
+            self,
+            Commands::DeleteNamespace { .. }
+                | Commands::Release { .. }
+                | Commands::Battle { .. }
+                | Commands::Restore { .. }
+                | Commands::Import {
+                    on_conflict: ConflictPolicy::Overwrite,
+                    ..
+                }
+                | Commands::Team {
+                    command: TeamCommands::Rm { .. }
+                }
+                | Commands::Undo { .. }
+                | Commands::Doctor { fix: true }
+        )
+    }
+
+    /// Commands whose changes are recorded in the journal so that `undo` can roll
+    /// them back.
+    pub fn is_journaled(&self) -> bool {
+        match self {
+            Commands::CreateNamespace { .. }
+            | Commands::DeleteNamespace { .. }
+            | Commands::Summon { .. }
+            | Commands::Start { .. }
+            | Commands::Stop { .. }
+            | Commands::Pause { .. }
+            | Commands::Release { .. }
+            | Commands::Battle { .. }
+            | Commands::Save { .. }
+            | Commands::Import { .. }
+            | Commands::ClaimReward { .. }
+            | Commands::Doctor { fix: true } => true,
+            Commands::Team { command } => !matches!(
+                command,
+                TeamCommands::Ls | TeamCommands::Show { .. }
+            ),
+            _ => false,
+        }
+    }
 }
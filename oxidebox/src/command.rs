@@ -31,6 +31,9 @@ impl CommandHandler {
                 defense,
                 speed,
                 pokemon_type,
+                secondary_type,
+                sp_attack,
+                sp_defense,
             } => {
                 self.container_manager.summon(
                     &namespace,
@@ -41,6 +44,9 @@ impl CommandHandler {
                     defense,
                     speed,
                     pokemon_type,
+                    secondary_type,
+                    sp_attack,
+                    sp_defense,
                 );
             }
             Commands::Start { id } => {
@@ -52,7 +58,7 @@ impl CommandHandler {
             Commands::Pause { id } => {
                 self.container_manager.pause_container(&id);
             }
-            Commands::List { namespace } => {
+            Commands::List { namespace, .. } => {
                 if let Some(ns) = namespace {
                     self.container_manager.list_containers(Some(ns.as_str()));
                 } else {
@@ -64,8 +70,25 @@ impl CommandHandler {
                     container.display_status();
                 }
             }
-            Commands::Battle { id1, id2 } => {
-                self.container_manager.battle(&id1, &id2, &self.evolution_manager);
+            Commands::Battle {
+                id1,
+                id2,
+                weather,
+                seed,
+                no_evolve,
+                interactive,
+                ai1,
+                ai2,
+            } => {
+                self.container_manager.battle(
+                    &id1,
+                    &id2,
+                    weather,
+                    seed,
+                    (!no_evolve).then_some(&self.evolution_manager),
+                    interactive,
+                    [ai1, ai2],
+                );
             }
             Commands::Save { id } => {
                 if let Err(e) = self.container_manager.save_to_db(&id) {
@@ -86,6 +109,19 @@ impl CommandHandler {
             Commands::DeleteNamespace { name } => {
                 self.container_manager.delete_namespace(&name);
             }
+            Commands::Challenges
+            | Commands::Replay { .. }
+            | Commands::ClaimReward { .. }
+            | Commands::Backup { .. }
+            | Commands::Restore { .. }
+            | Commands::Export { .. }
+            | Commands::Import { .. }
+            | Commands::Release { .. }
+            | Commands::Team { .. }
+            | Commands::Trainer { .. }
+            | Commands::Undo { .. }
+            | Commands::History { .. }
+            | Commands::Doctor { .. } => {}
         }
     }
 }
@@ -1,14 +1,25 @@
+use crate::ai::{AiStrategy, BattleAi};
 use crate::battle::Battle;
-use crate::database::Database;
-use crate::evolution::EvolutionManager;
-use crate::moves::{Move, PokemonType};
-use crate::stats::PokemonStats;
-use crate::stats::TrainerStats;
+use crate::bundle::{ConflictPolicy, NamespaceBundle};
+use crate::challenge::ClaimOutcome;
+use crate::database::{Database, ImportSummary};
+use crate::evolution::{Evolution, EvolutionManager};
+use crate::interactive::MoveMenu;
+use crate::moves::{Move, PokemonType, StatStages, StatusCondition, Weather};
+use crate::stats::{BattleRecord, PokemonStats, TrainerStats};
+use crate::storage::{PokemonPages, PokemonQuery, Storage, StorageError};
 use colored::*;
-use rusqlite::Result;
 use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Number of moves a Pokémon can know at once.
+pub const MAX_MOVES: usize = 4;
+
+/// Pokémon stop levelling up here.
+pub const MAX_LEVEL: u32 = 100;
+
 #[derive(Debug, Clone, PartialEq)]
 #[allow(dead_code)]
 pub enum ContainerState {
@@ -20,6 +31,22 @@ pub enum ContainerState {
     Evolved,
 }
 
+impl FromStr for ContainerState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Created" => Ok(ContainerState::Created),
+            "Running" => Ok(ContainerState::Running),
+            "Paused" => Ok(ContainerState::Paused),
+            "Stopped" => Ok(ContainerState::Stopped),
+            "Failed" => Ok(ContainerState::Failed),
+            "Evolved" => Ok(ContainerState::Evolved),
+            _ => Err(format!("Invalid container state: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ContainerResources {
     pub cpu_limit: f64,
@@ -40,8 +67,17 @@ pub struct Container {
     pub max_hp: i32,
     pub attack: u32,
     pub defense: u32,
+    /// Used instead of attack and defense for special moves.
+    pub sp_attack: u32,
+    pub sp_defense: u32,
     pub speed: u32,
     pub pokemon_type: PokemonType,
+    pub secondary_type: Option<PokemonType>,
+    /// Burn, poison and the like, picked up in battle. It stays after the battle.
+    pub status_condition: Option<StatusCondition>,
+    /// Stat changes from moves like Growl. They only last for one battle, so
+    /// they aren't saved.
+    pub stat_stages: StatStages,
     pub moves: Vec<Move>,
     pub exp: u32,
     pub exp_to_next_level: u32,
@@ -49,11 +85,13 @@ pub struct Container {
     pub resources: ContainerResources,
     pub created_at: SystemTime,
     pub namespace: String,
-    #[allow(dead_code)]
     pub labels: HashMap<String, String>,
+    /// Bumped by storage on every save to detect concurrent modification.
+    pub version: u32,
 }
 
 impl Container {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: &str,
         namespace: &str,
@@ -85,8 +123,13 @@ impl Container {
             max_hp: hp,
             attack,
             defense,
+            sp_attack: attack,
+            sp_defense: defense,
             speed,
             pokemon_type,
+            secondary_type: None,
+            status_condition: None,
+            stat_stages: StatStages::default(),
             moves: Vec::new(),
             exp: 0,
             exp_to_next_level,
@@ -102,31 +145,80 @@ impl Container {
             created_at: now,
             namespace: namespace.to_string(),
             labels,
+            version: 0,
         }
     }
 
     fn calculate_exp_to_next_level(level: u32) -> u32 {
-        (level * level * 100) as u32
+        level * level * 100
+    }
+
+    /// Adds `amount` EXP and levels up for every threshold it crosses, returning
+    /// the number of levels gained. Each level grows every stat by about the
+    /// share one level is worth, so a Pokémon's stats stay roughly proportional
+    /// to its level, and heals it to full HP.
+    pub fn gain_exp(&mut self, amount: u32) -> u32 {
+        self.exp += amount;
+        self.stats.total_exp_gained += amount;
+        let mut levels = 0;
+        while self.level < MAX_LEVEL && self.exp >= self.exp_to_next_level {
+            self.exp -= self.exp_to_next_level;
+            let growth = |stat: u32, level: u32| stat + (stat / level.max(1)).max(1);
+            self.max_hp = growth(self.max_hp as u32, self.level) as i32;
+            self.attack = growth(self.attack, self.level);
+            self.defense = growth(self.defense, self.level);
+            self.sp_attack = growth(self.sp_attack, self.level);
+            self.sp_defense = growth(self.sp_defense, self.level);
+            self.speed = growth(self.speed, self.level);
+            self.level += 1;
+            self.exp_to_next_level = Self::calculate_exp_to_next_level(self.level);
+            levels += 1;
+        }
+        if levels > 0 {
+            self.hp = self.max_hp;
+            self.stats.levels_gained += levels;
+        }
+        levels
     }
 
     pub fn is_active(&self) -> bool {
         self.hp > 0 && self.state == ContainerState::Running
     }
 
-    #[allow(dead_code)]
-    pub fn evolve(&mut self, new_form: &str) -> bool {
-        if self.level >= 30 {
-            self.name = new_form.to_string();
-            self.max_hp += 20;
-            self.hp = self.max_hp;
-            true
-        } else {
-            false
+    /// Turns the Pokémon into `evolution.to` if it has reached the evolution's
+    /// level. The attack and defense multipliers also apply to the special stats.
+    pub fn evolve(&mut self, evolution: &Evolution) -> bool {
+        if self.level < evolution.level {
+            return false;
+        }
+        let grow = |stat: u32, multiplier: f32| (stat as f32 * multiplier).round() as u32;
+        let multipliers = &evolution.stat_multipliers;
+        self.name = evolution.to.clone();
+        self.max_hp = grow(self.max_hp as u32, multipliers.hp) as i32;
+        self.hp = self.max_hp;
+        self.attack = grow(self.attack, multipliers.attack);
+        self.defense = grow(self.defense, multipliers.defense);
+        self.sp_attack = grow(self.sp_attack, multipliers.attack);
+        self.sp_defense = grow(self.sp_defense, multipliers.defense);
+        self.speed = grow(self.speed, multipliers.speed);
+        self.stats.evolution_count += 1;
+        true
+    }
+
+    pub fn has_type(&self, pokemon_type: PokemonType) -> bool {
+        self.pokemon_type == pokemon_type || self.secondary_type == Some(pokemon_type)
+    }
+
+    /// The type for display, like "Fire" or "Fire/Flying".
+    pub fn type_name(&self) -> String {
+        match self.secondary_type {
+            Some(secondary) => format!("{}/{}", self.pokemon_type, secondary),
+            None => self.pokemon_type.to_string(),
         }
     }
 
     pub fn learn_move(&mut self, mv: Move) -> bool {
-        if self.moves.len() < 4 {
+        if self.moves.len() < MAX_MOVES {
             self.moves.push(mv);
             true
         } else {
@@ -163,13 +255,19 @@ impl Container {
             "║ ID:      {:<32} ║\n\
              ║ State:   {:<32} ║\n\
              ║ Level:   {:<32} ║\n\
+             ║ EXP:     {:<32} ║\n\
              ║ HP:      {:<32} ║\n\
-             ║ Type:    {:<32} ║",
+             ║ Type:    {:<32} ║\n\
+             ║ Status:  {:<32} ║",
             self.id.bright_white(),
             format!("{:?}", self.state).bright_green(),
             self.level,
+            format!("{}/{}", self.exp, self.exp_to_next_level),
             format!("{}/{}", self.hp, self.max_hp),
-            self.pokemon_type.to_string().bright_magenta()
+            self.type_name().bright_magenta(),
+            self.status_condition
+                .map_or("Healthy".to_string(), |status| status.to_string())
+                .bright_red()
         );
         println!(
             "{}",
@@ -178,35 +276,77 @@ impl Container {
     }
 }
 
+const LIST_PAGE_SIZE: usize = 500;
+
+/// How many times a read-modify-write is replayed on fresh data after losing a
+/// race with another process before giving up.
+const MAX_CONFLICT_RETRIES: usize = 3;
+
 pub struct ContainerManager {
     containers: HashMap<String, Container>,
     namespaces: HashMap<String, Vec<String>>,
     pub trainer_stats: TrainerStats,
-    db: Database,
+    storage: Box<dyn Storage>,
 }
 
 impl ContainerManager {
     pub fn new() -> Self {
         let db = Database::new().expect("Failed to create database");
-        let mut namespaces = HashMap::new();
+        Self::with_storage(Box::new(db))
+    }
 
-        // Load existing namespaces from database
-        if let Ok(existing_namespaces) = db.get_namespaces() {
+    pub fn with_storage(storage: Box<dyn Storage>) -> Self {
+        let mut manager = ContainerManager {
+            containers: HashMap::new(),
+            namespaces: HashMap::new(),
+            trainer_stats: TrainerStats::new(),
+            storage,
+        };
+        manager.load_trainer();
+        manager
+    }
+
+    /// Loads the namespaces and stats of the storage's active trainer.
+    fn load_trainer(&mut self) {
+        self.containers.clear();
+        self.namespaces.clear();
+        if let Ok(existing_namespaces) = self.storage.get_namespaces() {
             for namespace in existing_namespaces {
-                namespaces.insert(namespace.clone(), Vec::new());
+                self.namespaces.insert(namespace, Vec::new());
             }
         }
+        self.trainer_stats = self
+            .storage
+            .load_trainer_stats()
+            .unwrap_or_else(|_| TrainerStats::new());
+    }
 
-        ContainerManager {
-            containers: HashMap::new(),
-            namespaces,
-            trainer_stats: TrainerStats::new(),
-            db,
+    pub fn trainer(&self) -> &str {
+        self.storage.trainer()
+    }
+
+    /// Acts as `trainer` for the rest of this session without changing the
+    /// current trainer. Returns false if the trainer doesn't exist.
+    pub fn use_trainer(&mut self, trainer: &str) -> Result<bool, StorageError> {
+        if !self.storage.list_trainers()?.iter().any(|t| t == trainer) {
+            return Ok(false);
+        }
+        self.storage.set_trainer(trainer);
+        self.load_trainer();
+        Ok(true)
+    }
+
+    /// Makes `trainer` the current trainer for this and later sessions.
+    pub fn switch_trainer(&mut self, trainer: &str) -> Result<bool, StorageError> {
+        if !self.storage.switch_trainer(trainer)? {
+            return Ok(false);
         }
+        self.load_trainer();
+        Ok(true)
     }
 
     pub fn create_namespace(&mut self, name: &str) -> bool {
-        if self.db.create_namespace(name).unwrap_or(false) {
+        if self.storage.create_namespace(name).unwrap_or(false) {
             self.namespaces.insert(name.to_string(), Vec::new());
             true
         } else {
@@ -215,7 +355,7 @@ impl ContainerManager {
     }
 
     pub fn delete_namespace(&mut self, name: &str) -> bool {
-        if self.db.delete_namespace(name).unwrap_or(false) {
+        if self.storage.delete_namespace(name).unwrap_or(false) {
             self.namespaces.remove(name);
             true
         } else {
@@ -223,6 +363,9 @@ impl ContainerManager {
         }
     }
 
+    /// Creates and persists a new container, returning the id assigned by storage.
+    /// Special attack and defense default to attack and defense.
+    #[allow(clippy::too_many_arguments)]
     pub fn summon(
         &mut self,
         namespace: &str,
@@ -233,12 +376,15 @@ impl ContainerManager {
         defense: u16,
         speed: u16,
         pokemon_type: PokemonType,
-    ) -> bool {
+        secondary_type: Option<PokemonType>,
+        sp_attack: Option<u16>,
+        sp_defense: Option<u16>,
+    ) -> Option<String> {
         if !self.namespaces.contains_key(namespace) {
-            return false;
+            return None;
         }
 
-        let container = Container::new(
+        let mut container = Container::new(
             name,
             namespace,
             level as u32,
@@ -248,49 +394,94 @@ impl ContainerManager {
             speed as u32,
             pokemon_type,
         );
+        container.secondary_type = secondary_type.filter(|t| *t != pokemon_type);
+        container.sp_attack = sp_attack.unwrap_or(attack) as u32;
+        container.sp_defense = sp_defense.unwrap_or(defense) as u32;
 
-        // Save to database
-        if let Err(_) = self.db.save_pokemon(&container) {
-            return false;
-        }
+        // Save to storage
+        self.storage.save_pokemon(&mut container).ok()?;
 
         // Update trainer stats
         self.trainer_stats.total_pokemon_caught += 1;
+        let _ = self.storage.save_trainer_stats(&self.trainer_stats);
 
+        let id = container.id.clone();
+        self.containers.insert(id.clone(), container);
+        Some(id)
+    }
+
+    /// Deletes a container for good, dropping it from any team it belonged to.
+    pub fn release(&mut self, id: &str) -> bool {
+        if !self.storage.delete_pokemon(id).unwrap_or(false) {
+            return false;
+        }
+        self.containers.remove(id);
+        self.trainer_stats.total_pokemon_released += 1;
+        let _ = self.storage.save_trainer_stats(&self.trainer_stats);
         true
     }
 
     pub fn start_container(&mut self, id: &str) -> bool {
-        if let Some(container) = self.containers.get_mut(id) {
-            container.state = ContainerState::Running;
-            true
-        } else {
-            false
-        }
+        self.set_state(id, ContainerState::Running)
     }
 
     pub fn stop_container(&mut self, id: &str) -> bool {
-        if let Some(container) = self.containers.get_mut(id) {
-            container.state = ContainerState::Stopped;
-            true
-        } else {
-            false
-        }
+        self.set_state(id, ContainerState::Stopped)
     }
 
     pub fn pause_container(&mut self, id: &str) -> bool {
-        if let Some(container) = self.containers.get_mut(id) {
-            container.state = ContainerState::Paused;
-            true
-        } else {
-            false
+        self.set_state(id, ContainerState::Paused)
+    }
+
+    fn set_state(&mut self, id: &str, state: ContainerState) -> bool {
+        self.update(id, |container| container.state = state.clone())
+    }
+
+    /// Applies `change` to the container and saves it. If another process saved the
+    /// container in the meantime, the change is reapplied to a fresh copy.
+    fn update(&mut self, id: &str, mut change: impl FnMut(&mut Container)) -> bool {
+        for _ in 0..MAX_CONFLICT_RETRIES {
+            let Some(mut container) = self.fetch(id).cloned() else {
+                return false;
+            };
+            change(&mut container);
+            match self.storage.save_pokemon(&mut container) {
+                Ok(_) => {
+                    self.containers.insert(id.to_string(), container);
+                    return true;
+                }
+                Err(StorageError::Conflict) => {
+                    self.containers.remove(id);
+                }
+                Err(e) => {
+                    println!(
+                        "{}",
+                        format!("⚠️ Failed to save container {}: {}", id, e).bright_red()
+                    );
+                    return false;
+                }
+            }
         }
+        println!(
+            "{}",
+            format!("⚠️ Container {} keeps changing, try again later", id).bright_red()
+        );
+        false
     }
 
-    pub fn get_container(&self, id: &str) -> Option<&Container> {
-        self.containers.get(id)
+    /// Returns the container from the in-memory cache, loading it from storage first
+    /// if this process hasn't seen it yet.
+    fn fetch(&mut self, id: &str) -> Option<&mut Container> {
+        if !self.containers.contains_key(id) {
+            let container = self.storage.load_pokemon(id).ok()??;
+            self.containers.insert(id.to_string(), container);
+        }
+        self.containers.get_mut(id)
     }
 
+    pub fn get_container(&mut self, id: &str) -> Option<&Container> {
+        self.fetch(id).map(|container| &*container)
+    }
     #[allow(dead_code)]
     pub fn get_container_mut(&mut self, id: &str) -> Option<&mut Container> {
         self.containers.get_mut(id)
@@ -315,72 +506,191 @@ impl ContainerManager {
         println!("{}", "=====================".bright_cyan());
     }
 
-    pub fn battle(&mut self, id1: &str, id2: &str, evolution_manager: &EvolutionManager) -> bool {
+    #[allow(clippy::too_many_arguments)]
+    pub fn battle(
+        &mut self,
+        id1: &str,
+        id2: &str,
+        weather: Option<Weather>,
+        seed: Option<u64>,
+        evolution_manager: Option<&EvolutionManager>,
+        interactive: bool,
+        ais: [AiStrategy; 2],
+    ) -> Option<BattleRecord> {
         if id1 == id2 {
             println!("{}", "⚠️ A Pokemon cannot battle itself!".bright_red());
-            return false;
+            return None;
         }
 
-        let p1 = self.containers.remove(id1);
-        let p2 = self.containers.remove(id2);
+        let seed = seed.unwrap_or_else(rand::random);
+        println!(
+            "{}",
+            format!("🎲 Battle seed: {} (replay with --seed {})", seed, seed).bright_blue()
+        );
+        let mut menu = interactive.then(|| MoveMenu::new(io::stdin().lock(), io::stdout()));
 
-        match (p1, p2) {
-            (Some(mut p1), Some(mut p2)) => {
-                if p1.state != ContainerState::Running || p2.state != ContainerState::Running {
+        for attempt in 0..MAX_CONFLICT_RETRIES {
+            if attempt > 0 {
+                // Someone else saved one of the Pokémon mid-battle; replay the
+                // battle with their latest state.
+                println!(
+                    "{}",
+                    "🔄 A Pokemon changed during the battle, replaying with fresh data..."
+                        .bright_yellow()
+                );
+                self.containers.remove(id1);
+                self.containers.remove(id2);
+            }
+
+            let p1 = self.fetch(id1).cloned();
+            let p2 = self.fetch(id2).cloned();
+            let (mut p1, mut p2) = match (p1, p2) {
+                (Some(p1), Some(p2)) => (p1, p2),
+                (Some(_), None) => {
+                    println!("{}", "⚠️ Second Pokemon not found!".bright_red());
+                    return None;
+                }
+                (None, Some(_)) => {
+                    println!("{}", "⚠️ First Pokemon not found!".bright_red());
+                    return None;
+                }
+                (None, None) => {
+                    println!("{}", "⚠️ Both Pokemon not found!".bright_red());
+                    return None;
+                }
+            };
+            if p1.state != ContainerState::Running || p2.state != ContainerState::Running {
+                println!(
+                    "{}",
+                    "⚠️ Both Pokemon must be running to battle!".bright_red()
+                );
+                return None;
+            }
+
+            let mut rng = Battle::seeded_rng(seed);
+            let (mut ai1, mut ai2) = (ais[0].build(), ais[1].build());
+            let challenger: &mut dyn BattleAi = match &mut menu {
+                Some(menu) => menu,
+                None => ai1.as_mut(),
+            };
+            let mut record = Battle::start_battle(
+                &mut p1,
+                &mut p2,
+                weather,
+                &mut rng,
+                evolution_manager,
+                [challenger, ai2.as_mut()],
+            );
+            record.seed = Some(seed);
+            match self.storage.save_battle(&mut p1, &mut p2, &record) {
+                Ok(_) => {
+                    self.containers.insert(id1.to_string(), p1);
+                    self.containers.insert(id2.to_string(), p2);
+                    if let Ok(stats) = self.storage.load_trainer_stats() {
+                        self.trainer_stats = stats;
+                    }
+                    return Some(record);
+                }
+                // Replaying would ask the user to play every turn again, so an
+                // interactive battle is given up instead.
+                Err(StorageError::Conflict) if interactive => {
                     println!(
                         "{}",
-                        "⚠️ Both Pokemon must be running to battle!".bright_red()
+                        "⚠️ A Pokemon changed during the battle, so the result wasn't saved"
+                            .bright_red()
                     );
-                    self.containers.insert(id1.to_string(), p1);
-                    self.containers.insert(id2.to_string(), p2);
-                    return false;
+                    return None;
+                }
+                Err(StorageError::Conflict) => continue,
+                Err(e) => {
+                    println!(
+                        "{}",
+                        format!("⚠️ Failed to save the battle: {}", e).bright_red()
+                    );
+                    return None;
                 }
-
-                Battle::start_battle(&mut p1, &mut p2, evolution_manager);
-                self.containers.insert(id1.to_string(), p1);
-                self.containers.insert(id2.to_string(), p2);
-                true
-            }
-            (Some(p1), None) => {
-                println!("{}", "⚠️ Second Pokemon not found!".bright_red());
-                self.containers.insert(id1.to_string(), p1);
-                false
-            }
-            (None, Some(p2)) => {
-                println!("{}", "⚠️ First Pokemon not found!".bright_red());
-                self.containers.insert(id2.to_string(), p2);
-                false
-            }
-            (None, None) => {
-                println!("{}", "⚠️ Both Pokemon not found!".bright_red());
-                false
             }
         }
+        println!(
+            "{}",
+            "⚠️ The Pokemon keep changing, try the battle again later".bright_red()
+        );
+        None
     }
 
-    pub fn save_to_db(&self, id: &str) -> Result<(), rusqlite::Error> {
-        let mut db = Database::new()?;
-        if let Some(pokemon) = self.containers.get(id) {
-            db.save_pokemon(pokemon)?;
+    pub fn save_to_db(&mut self, id: &str) -> Result<(), StorageError> {
+        if let Some(pokemon) = self.containers.get_mut(id) {
+            self.storage.save_pokemon(pokemon)?;
         }
         Ok(())
     }
 
-    pub fn load_from_db(&mut self, id: &str) -> Result<(), rusqlite::Error> {
-        let db = Database::new()?;
-        if let Some(pokemon) = db.load_pokemon(id)? {
+    pub fn load_from_db(&mut self, id: &str) -> Result<(), StorageError> {
+        if let Some(pokemon) = self.storage.load_pokemon(id)? {
             self.containers.insert(id.to_string(), pokemon);
         }
         Ok(())
     }
 
+    pub fn export_namespace(&self, namespace: &str) -> Result<NamespaceBundle, String> {
+        if !self.namespaces.contains_key(namespace) {
+            return Err(format!("namespace {} not found", namespace));
+        }
+        let containers = self
+            .storage
+            .load_namespace_pokemon(namespace)
+            .map_err(|e| e.to_string())?;
+        Ok(NamespaceBundle::new(namespace, &containers))
+    }
+
+    pub fn import_bundle(
+        &mut self,
+        bundle: &NamespaceBundle,
+        into: Option<&str>,
+        on_conflict: ConflictPolicy,
+    ) -> Result<ImportSummary, String> {
+        let namespace = into.unwrap_or(&bundle.namespace);
+        if let Some(owner) = self
+            .storage
+            .namespace_owner(namespace)
+            .map_err(|e| e.to_string())?
+        {
+            if owner != self.storage.trainer() {
+                return Err(format!(
+                    "namespace {} belongs to trainer {}",
+                    namespace, owner
+                ));
+            }
+        }
+        let containers = bundle.to_containers(namespace);
+        let summary = self
+            .storage
+            .import_pokemon(namespace, &containers, on_conflict)
+            .map_err(|e| e.to_string())?;
+        self.namespaces.entry(namespace.to_string()).or_default();
+        Ok(summary)
+    }
+
+    /// Claims a completed challenge's reward, crediting its EXP to the trainer.
+    pub fn claim_challenge(&mut self, id: &str) -> Result<ClaimOutcome, StorageError> {
+        let outcome = self.storage.claim_challenge(id)?;
+        self.trainer_stats = self.storage.load_trainer_stats()?;
+        Ok(outcome)
+    }
+
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    pub fn storage_mut(&mut self) -> &mut dyn Storage {
+        self.storage.as_mut()
+    }
+
     pub fn display_stats(&self) {
         self.trainer_stats.display_detailed_stats();
     }
 
-    pub fn list_all_from_db() -> Result<(), rusqlite::Error> {
-        let db = Database::new()?;
-        let pokemons = db.load_all_pokemon()?;
+    pub fn list_all_from_db(&self, query: PokemonQuery) -> Result<(), StorageError> {
         println!(
             "{}",
             "╔════════════════════════════════════════════════════════╗".bright_blue()
@@ -395,27 +705,31 @@ impl ContainerManager {
             "{}",
             "╠════════════════════════════════════════════════════════╣".bright_blue()
         );
-        if pokemons.is_empty() {
-            println!(
-                "{}",
-                "║        No Pokémon containers found!                 ║".bright_red()
-            );
-        } else {
-            for pokemon in pokemons {
+        let mut found = false;
+        for page in PokemonPages::new(self.storage.as_ref(), query, LIST_PAGE_SIZE) {
+            for pokemon in page? {
+                found = true;
                 println!(
                     "{}",
                     format!(
-                        "║ {:<12} | Lv.{:<2} | HP:{:<3} | Type:{:<10} | State:{:<8} ║",
+                        "║ {:<4} | {:<12} | Lv.{:<2} | HP:{:<3} | Type:{:<10} | State:{:<8} ║",
+                        pokemon.id.bright_white(),
                         pokemon.name.bright_yellow(),
                         pokemon.level,
                         pokemon.hp,
-                        pokemon.pokemon_type.to_string().bright_magenta(),
+                        pokemon.type_name().bright_magenta(),
                         format!("{:?}", pokemon.state).bright_green()
                     )
                     .bold()
                 );
             }
         }
+        if !found {
+            println!(
+                "{}",
+                "║        No Pokémon containers found!                 ║".bright_red()
+            );
+        }
         println!(
             "{}",
             "╚════════════════════════════════════════════════════════╝".bright_blue()
@@ -423,3 +737,199 @@ impl ContainerManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::MoveCategory;
+    use crate::storage::{MemoryStorage, DEFAULT_TRAINER};
+
+    fn manager() -> ContainerManager {
+        let mut manager = ContainerManager::with_storage(Box::new(MemoryStorage::new()));
+        manager.create_namespace("kanto");
+        manager
+    }
+
+    fn tackle() -> Move {
+        Move::new(
+            "Tackle",
+            40,
+            100,
+            35,
+            PokemonType::Normal,
+            MoveCategory::Physical,
+            "A full-body charge",
+            None,
+        )
+    }
+
+    #[test]
+    fn test_lifecycle_is_persisted() {
+        let mut manager = manager();
+        let id = manager
+            .summon(
+                "kanto",
+                "Pikachu",
+                5,
+                35,
+                55,
+                40,
+                90,
+                PokemonType::Electric,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(manager
+            .summon(
+                "johto",
+                "Totodile",
+                5,
+                50,
+                65,
+                64,
+                43,
+                PokemonType::Water,
+                None,
+                None,
+                None,
+            )
+            .is_none());
+
+        assert!(manager.start_container(&id));
+        assert!(manager.pause_container(&id));
+        assert!(!manager.start_container("missing"));
+
+        let stored = manager.storage.load_pokemon(&id).unwrap().unwrap();
+        assert_eq!(stored.state, ContainerState::Paused);
+    }
+
+    #[test]
+    fn test_battle_requires_running_containers() {
+        let mut manager = manager();
+        let a = manager
+            .summon(
+                "kanto",
+                "Rattata",
+                3,
+                30,
+                56,
+                35,
+                72,
+                PokemonType::Normal,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let b = manager
+            .summon(
+                "kanto",
+                "Pidgey",
+                3,
+                40,
+                45,
+                40,
+                56,
+                PokemonType::Flying,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        for id in [&a, &b] {
+            manager.get_container_mut(id).unwrap().learn_move(tackle());
+        }
+
+        assert!(manager
+            .battle(
+                &a,
+                &b,
+                None,
+                None,
+                Some(&EvolutionManager::new()),
+                false,
+                [AiStrategy::Random; 2]
+            )
+            .is_none());
+        manager.start_container(&a);
+        manager.start_container(&b);
+        assert!(manager
+            .battle(
+                &a,
+                &b,
+                None,
+                None,
+                Some(&EvolutionManager::new()),
+                false,
+                [AiStrategy::Random; 2]
+            )
+            .is_some());
+
+        let a = manager.storage.load_pokemon(&a).unwrap().unwrap();
+        let b = manager.storage.load_pokemon(&b).unwrap().unwrap();
+        assert_eq!(a.stats.battles_won + b.stats.battles_won, 1);
+        assert!(a.hp <= 0 || b.hp <= 0);
+        assert_eq!(a.exp + b.exp, Battle::calculate_exp_reward(3));
+
+        assert_eq!(manager.trainer_stats.total_battles, 1);
+        assert_eq!(manager.trainer_stats.total_wins, a.stats.battles_won);
+        assert_eq!(manager.trainer_stats.favorite_pokemon_type, "Normal");
+        assert_eq!(manager.trainer_stats.most_used_move, "Tackle");
+    }
+
+    #[test]
+    fn test_other_trainers_pokemon_are_out_of_reach() {
+        let mut manager = manager();
+        let id = manager
+            .summon(
+                "kanto",
+                "Pikachu",
+                5,
+                35,
+                55,
+                40,
+                90,
+                PokemonType::Electric,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        manager.storage.create_trainer("misty").unwrap();
+        assert!(manager.use_trainer("misty").unwrap());
+
+        assert!(!manager.release(&id));
+        assert!(!manager.start_container(&id));
+        assert!(manager.get_container(&id).is_none());
+        assert_eq!(manager.trainer_stats.total_pokemon_released, 0);
+
+        assert!(manager.use_trainer(DEFAULT_TRAINER).unwrap());
+        assert!(manager.release(&id));
+    }
+
+    #[test]
+    fn test_exp_levels_up_and_grows_stats() {
+        let mut pokemon = Container::new("Pidgey", "kanto", 2, 40, 45, 40, 56, PokemonType::Flying);
+        pokemon.hp = 10;
+        assert_eq!(pokemon.gain_exp(300), 0);
+        assert_eq!((pokemon.level, pokemon.exp, pokemon.hp), (2, 300, 10));
+
+        // 400 EXP reaches level 3, and 900 more are needed for level 4.
+        assert_eq!(pokemon.gain_exp(900), 1);
+        assert_eq!(pokemon.level, 3);
+        assert_eq!((pokemon.exp, pokemon.exp_to_next_level), (800, 900));
+        assert_eq!(
+            (pokemon.max_hp, pokemon.attack, pokemon.speed),
+            (60, 67, 84)
+        );
+        assert_eq!(pokemon.hp, pokemon.max_hp);
+        assert_eq!(pokemon.gain_exp(100), 1);
+        assert_eq!(pokemon.stats.levels_gained, 2);
+        assert_eq!(pokemon.stats.total_exp_gained, 1300);
+
+        pokemon.level = MAX_LEVEL;
+        assert_eq!(pokemon.gain_exp(u32::MAX / 2), 0);
+    }
+}
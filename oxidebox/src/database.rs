@@ -1,79 +1,380 @@
-use crate::container::Container;
+use crate::bundle::ConflictPolicy;
+use crate::challenge::{Challenge, ClaimOutcome};
+use crate::container::{Container, ContainerState};
+use crate::doctor::{self, Problem};
+use crate::journal::{self, JournalEntry, UndoOutcome};
 use crate::moves::{Move, PokemonType};
-use rusqlite::params;
-use rusqlite::{Connection, OptionalExtension, Result};
+use crate::stats::{BattleRecord, TrainerStats};
+use crate::storage::{self, PokemonQuery, Storage, StorageError, DEFAULT_TRAINER, RECENT_BATTLES};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter};
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension, Result, Row};
+use rusqlite::{Transaction, TransactionBehavior};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DB_PATH: &str = "pokemon.db";
+pub const SCHEMA_VERSION: i32 = 11;
+
+const BACKUP_DIR: &str = "backups";
+
+/// How long a connection waits for another process's write lock before giving up
+/// with SQLITE_BUSY.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Entry N upgrades the schema from version N to N + 1. The first entry is
+// empty because version 1 is the schema created by `create_tables`.
+const MIGRATIONS: &[&str] = &[
+    "",
+    "ALTER TABLE pokemon ADD COLUMN max_hp INTEGER;
+     ALTER TABLE pokemon ADD COLUMN labels TEXT;
+     ALTER TABLE pokemon ADD COLUMN stats TEXT;
+     UPDATE pokemon SET max_hp = hp WHERE max_hp IS NULL;",
+    "CREATE INDEX IF NOT EXISTS idx_pokemon_namespace ON pokemon (namespace);
+     CREATE INDEX IF NOT EXISTS idx_pokemon_level ON pokemon (level);
+     CREATE INDEX IF NOT EXISTS idx_pokemon_type ON pokemon (pokemon_type);
+     CREATE INDEX IF NOT EXISTS idx_moves_pokemon_id ON moves (pokemon_id);",
+    "INSERT OR IGNORE INTO trainers (name, created_at) VALUES ('default', strftime('%s', 'now'));
+     ALTER TABLE namespaces ADD COLUMN owner TEXT;
+     UPDATE namespaces SET owner = 'default' WHERE owner IS NULL;
+     ALTER TABLE battle_records ADD COLUMN trainer TEXT;
+     UPDATE battle_records SET trainer = 'default' WHERE trainer IS NULL;
+     CREATE INDEX IF NOT EXISTS idx_battle_records_trainer ON battle_records (trainer);",
+    "ALTER TABLE pokemon ADD COLUMN version INTEGER NOT NULL DEFAULT 0;",
+    "ALTER TABLE pokemon ADD COLUMN secondary_type TEXT;",
+    "ALTER TABLE pokemon ADD COLUMN status_condition TEXT;
+     ALTER TABLE moves ADD COLUMN effect TEXT;",
+    "ALTER TABLE pokemon ADD COLUMN sp_attack INTEGER;
+     ALTER TABLE pokemon ADD COLUMN sp_defense INTEGER;
+     UPDATE pokemon SET sp_attack = attack, sp_defense = defense;
+     ALTER TABLE moves ADD COLUMN category TEXT;",
+    "ALTER TABLE battle_records ADD COLUMN seed INTEGER;",
+    "ALTER TABLE battle_records ADD COLUMN events TEXT;",
+    // Teams get an owner, which becomes part of their key. SQLite can't change a
+    // primary key in place, so both tables are rebuilt, keeping their rowids for
+    // the journal.
+    "ALTER TABLE team_members RENAME TO team_members_old;
+     ALTER TABLE teams RENAME TO teams_old;
+     CREATE TABLE teams (
+         owner TEXT NOT NULL DEFAULT 'default',
+         name TEXT NOT NULL,
+         created_at INTEGER NOT NULL,
+         PRIMARY KEY (owner, name)
+     );
+     CREATE TABLE team_members (
+         owner TEXT NOT NULL DEFAULT 'default',
+         team TEXT NOT NULL,
+         pokemon_id INTEGER NOT NULL,
+         position INTEGER NOT NULL,
+         PRIMARY KEY (owner, team, pokemon_id),
+         FOREIGN KEY(owner, team) REFERENCES teams(owner, name),
+         FOREIGN KEY(pokemon_id) REFERENCES pokemon(id)
+     );
+     INSERT INTO teams (rowid, name, created_at) SELECT rowid, name, created_at FROM teams_old;
+     INSERT INTO team_members (rowid, team, pokemon_id, position)
+         SELECT rowid, team, pokemon_id, position FROM team_members_old;
+     DROP TABLE team_members_old;
+     DROP TABLE teams_old;",
+];
 
 pub struct Database {
     conn: Connection,
+    path: PathBuf,
+    trainer: String,
 }
 
 impl Database {
     pub fn new() -> Result<Self> {
-        let conn = Connection::open("pokemon.db")?;
+        Self::open(DB_PATH)
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS namespaces (
-                name TEXT PRIMARY KEY
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path.as_ref())?;
+        configure_connection(&conn)?;
+
+        create_tables(&conn)?;
+
+        let mut db = Database {
+            conn,
+            path: path.as_ref().to_path_buf(),
+            trainer: DEFAULT_TRAINER.to_string(),
+        };
+        db.migrate_database()?;
+        journal::install_triggers(&db.conn)?;
+        db.trainer = db.current_trainer()?;
+        Ok(db)
+    }
+
+    pub fn migrate_database(&mut self) -> Result<()> {
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS migrations (
+                version INTEGER PRIMARY KEY,
+                applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
             )",
             [],
         )?;
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS pokemon (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                level INTEGER,
-                hp INTEGER,
-                attack INTEGER,
-                defense INTEGER,
-                speed INTEGER,
-                pokemon_type TEXT,
-                status TEXT,
-                exp INTEGER,
-                exp_to_next_level INTEGER,
-                namespace TEXT,
-                created_at INTEGER,
-                FOREIGN KEY(namespace) REFERENCES namespaces(name)
-            )",
-            [],
+        // The version is read inside the write transaction so that two processes
+        // opening an old database at once don't both apply the same migration.
+        let tx = write_transaction(&mut self.conn)?;
+        let current: i32 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        for (index, sql) in MIGRATIONS.iter().enumerate().skip(current.max(0) as usize) {
+            let version = index as i32 + 1;
+            tx.execute_batch(sql)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO migrations (version) VALUES (?1)",
+                params![version],
+            )?;
+            tx.pragma_update(None, "user_version", version)?;
+        }
+        tx.commit()
+    }
+
+    /// Copies the live database to `backup_path` using SQLite's online backup API,
+    /// so the copy is consistent even while other connections are writing.
+    pub fn backup_database<P: AsRef<Path>>(&self, backup_path: P) -> Result<()> {
+        self.conn.backup(DatabaseName::Main, backup_path, None)
+    }
+
+    /// Replaces the live database with the contents of `source`.
+    ///
+    /// The source must pass `PRAGMA integrity_check` and carry a schema version this
+    /// build understands. The previous database is kept next to the live one with a
+    /// `.rollback` suffix and is returned on success.
+    pub fn restore_database<P: AsRef<Path>>(&mut self, source: P) -> Result<PathBuf, String> {
+        let source = source.as_ref();
+        if !source.is_file() {
+            return Err(format!("{} does not exist", source.display()));
+        }
+
+        let src = Connection::open_with_flags(source, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| e.to_string())?;
+        let integrity: String = src
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .map_err(|e| format!("{} is not a valid database: {}", source.display(), e))?;
+        if integrity != "ok" {
+            return Err(format!("integrity check failed: {}", integrity));
+        }
+
+        let version: i32 = src
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "backup has schema version {} but this build supports up to {}",
+                version, SCHEMA_VERSION
+            ));
+        }
+        let has_pokemon: bool = src
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'pokemon')",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !has_pokemon {
+            return Err(format!("{} is not an OxideBox database", source.display()));
+        }
+        drop(src);
+
+        let rollback = self.rollback_path();
+        self.backup_database(&rollback)
+            .map_err(|e| format!("failed to write rollback copy: {}", e))?;
+        self.conn
+            .restore(
+                DatabaseName::Main,
+                source,
+                None::<fn(rusqlite::backup::Progress)>,
+            )
+            .map_err(|e| e.to_string())?;
+        configure_connection(&self.conn).map_err(|e| e.to_string())?;
+        create_tables(&self.conn).map_err(|e| e.to_string())?;
+        self.migrate_database().map_err(|e| e.to_string())?;
+        journal::install_triggers(&self.conn).map_err(|e| e.to_string())?;
+        if !self.list_trainers().map_err(|e| e.to_string())?.contains(&self.trainer) {
+            self.trainer = self.current_trainer().map_err(|e| e.to_string())?;
+        }
+        Ok(rollback)
+    }
+
+    pub fn rollback_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".rollback");
+        PathBuf::from(name)
+    }
+
+    /// The trainer chosen with `trainer switch`, falling back to the default trainer
+    /// when none was chosen or the chosen one no longer exists.
+    pub fn current_trainer(&self) -> Result<String> {
+        self.conn.query_row(
+            "SELECT COALESCE(
+                 (SELECT t.name FROM settings s JOIN trainers t ON t.name = s.value
+                  WHERE s.key = 'current_trainer'),
+                 ?1)",
+            params![DEFAULT_TRAINER],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_auto_backup_limit(&self, keep: usize) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('auto_backup', ?1)",
+            params![keep.to_string()],
         )?;
+        Ok(())
+    }
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS moves (
-                id INTEGER PRIMARY KEY,
-                pokemon_id INTEGER,
-                name TEXT NOT NULL,
-                pokemon_type TEXT,
-                power INTEGER,
-                accuracy INTEGER,
-                pp INTEGER,
-                max_pp INTEGER,
-                description TEXT,
-                FOREIGN KEY(pokemon_id) REFERENCES pokemon(id)
-            )",
-            [],
+    pub fn auto_backup_limit(&self) -> Result<usize> {
+        let value: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = 'auto_backup'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value.and_then(|v| v.parse().ok()).unwrap_or(0))
+    }
+
+    /// Takes a timestamped backup if `backup --auto N` is enabled and prunes the
+    /// backup directory down to the newest N files.
+    pub fn auto_backup(&self) -> Result<Option<PathBuf>, String> {
+        let keep = self.auto_backup_limit().map_err(|e| e.to_string())?;
+        if keep == 0 {
+            return Ok(None);
+        }
+
+        let dir = self.backup_dir();
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let prefix = self.backup_prefix();
+        let target = dir.join(format!(
+            "{}{}.db",
+            prefix,
+            Utc::now().format("%Y%m%d-%H%M%S%3f")
+        ));
+        self.backup_database(&target).map_err(|e| e.to_string())?;
+
+        let mut existing: Vec<PathBuf> = fs::read_dir(&dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".db"))
+            })
+            .collect();
+        existing.sort();
+        let excess = existing.len().saturating_sub(keep);
+        for old in &existing[..excess] {
+            fs::remove_file(old).map_err(|e| e.to_string())?;
+        }
+
+        Ok(Some(target))
+    }
+
+    /// Fills in the moves each side used, which are kept in `battle_moves`.
+    fn load_battle_moves(&self, record: &mut BattleRecord) -> Result<()> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT pokemon_id, move_name, uses FROM battle_moves WHERE battle_id = ?1",
         )?;
+        let moves = stmt.query_map(params![record.id], |row| {
+            Ok((row.get::<_, i64>(0)?.to_string(), row.get(1)?, row.get(2)?))
+        })?;
+        for row in moves {
+            let (pokemon_id, move_name, uses): (String, String, u32) = row?;
+            if pokemon_id == record.challenger_id {
+                record.challenger_moves.insert(move_name, uses);
+            } else {
+                record.opponent_moves.insert(move_name, uses);
+            }
+        }
+        Ok(())
+    }
 
-        Ok(Database { conn })
+    fn backup_dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(BACKUP_DIR)
     }
 
-    pub fn create_namespace(&mut self, name: &str) -> Result<bool> {
-        let result = self
-            .conn
-            .execute("INSERT INTO namespaces (name) VALUES (?1)", params![name]);
-        Ok(result.is_ok())
+    fn backup_prefix(&self) -> String {
+        let stem = self
+            .path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("pokemon");
+        format!("{}-", stem)
     }
+}
 
-    pub fn delete_namespace(&mut self, name: &str) -> Result<bool> {
-        let result = self
-            .conn
-            .execute("DELETE FROM namespaces WHERE name = ?1", params![name]);
+impl Storage for Database {
+    fn trainer(&self) -> &str {
+        &self.trainer
+    }
+
+    fn set_trainer(&mut self, name: &str) {
+        self.trainer = name.to_string();
+    }
+
+    fn create_trainer(&mut self, name: &str) -> storage::Result<bool> {
+        let created = self.conn.execute(
+            "INSERT OR IGNORE INTO trainers (name, created_at) VALUES (?1, ?2)",
+            params![name, Utc::now().timestamp()],
+        )?;
+        Ok(created > 0)
+    }
+
+    fn list_trainers(&self) -> storage::Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT name FROM trainers ORDER BY name")?;
+        let trainers = stmt.query_map([], |row| row.get(0))?;
+        Ok(trainers.collect::<Result<_>>()?)
+    }
+
+    fn switch_trainer(&mut self, name: &str) -> storage::Result<bool> {
+        if !self.list_trainers()?.iter().any(|trainer| trainer == name) {
+            return Ok(false);
+        }
+        self.conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('current_trainer', ?1)",
+            params![name],
+        )?;
+        self.trainer = name.to_string();
+        Ok(true)
+    }
+
+    fn load_inventory(&self) -> storage::Result<Vec<(String, u32)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT item, quantity FROM inventory WHERE trainer = ?1 AND quantity > 0 ORDER BY item",
+        )?;
+        let items = stmt.query_map(params![self.trainer], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(items.collect::<Result<_>>()?)
+    }
+
+    fn create_namespace(&mut self, name: &str) -> storage::Result<bool> {
+        let result = self.conn.execute(
+            "INSERT INTO namespaces (name, owner) VALUES (?1, ?2)",
+            params![name, self.trainer],
+        );
         Ok(result.is_ok())
     }
 
-    pub fn get_namespaces(&self) -> Result<Vec<String>> {
-        let mut stmt = self.conn.prepare("SELECT name FROM namespaces")?;
-        let namespaces = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    fn delete_namespace(&mut self, name: &str) -> storage::Result<bool> {
+        let result = self.conn.execute(
+            "DELETE FROM namespaces WHERE name = ?1 AND owner = ?2",
+            params![name, self.trainer],
+        );
+        Ok(result.is_ok_and(|deleted| deleted > 0))
+    }
+
+    fn get_namespaces(&self) -> storage::Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM namespaces WHERE owner = ?1")?;
+        let namespaces = stmt.query_map(params![self.trainer], |row| row.get::<_, String>(0))?;
         let mut result = Vec::new();
         for namespace in namespaces {
             result.push(namespace?);
@@ -81,180 +382,1742 @@ impl Database {
         Ok(result)
     }
 
-    pub fn save_pokemon(&mut self, container: &Container) -> Result<()> {
-        let tx = self.conn.transaction()?;
+    fn namespace_owner(&self, name: &str) -> storage::Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT owner FROM namespaces WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn save_pokemon(&mut self, container: &mut Container) -> storage::Result<String> {
+        let tx = write_transaction(&mut self.conn)?;
+        upsert_pokemon(&tx, container)?;
+        tx.commit()?;
+        Ok(container.id.clone())
+    }
 
+    fn delete_pokemon(&mut self, id: &str) -> storage::Result<bool> {
+        let tx = write_transaction(&mut self.conn)?;
+        let owned = tx
+            .query_row(
+                &format!("SELECT 1 FROM pokemon WHERE id = ?1 AND {}", OWNED_BY_TRAINER),
+                params![id, self.trainer],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if owned.is_none() {
+            return Ok(false);
+        }
+        tx.execute("DELETE FROM moves WHERE pokemon_id = ?1", params![id])?;
         tx.execute(
-            "INSERT INTO pokemon (name, level, hp, attack, defense, speed, pokemon_type, status, exp, exp_to_next_level, namespace, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            "DELETE FROM team_members WHERE pokemon_id = ?1",
+            params![id],
+        )?;
+        let deleted = tx.execute("DELETE FROM pokemon WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    fn load_pokemon(&self, id: &str) -> storage::Result<Option<Container>> {
+        let sql = format!(
+            "SELECT {} FROM pokemon WHERE id = ?1 AND {}",
+            POKEMON_COLUMNS, OWNED_BY_TRAINER
+        );
+        let pokemon = self
+            .conn
+            .query_row(&sql, params![id, self.trainer], row_to_container)
+            .optional()?;
+
+        if let Some(mut container) = pokemon {
+            for move_ in self.load_moves(id)? {
+                container.learn_move(move_);
+            }
+            Ok(Some(container))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn load_moves(&self, id: &str) -> storage::Result<Vec<Move>> {
+        let sql = format!(
+            "SELECT {} FROM moves WHERE pokemon_id = ?1 ORDER BY id",
+            MOVE_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let moves = stmt.query_map(params![id], row_to_move)?;
+        Ok(moves
+            .map(|row| row.map(|(_, move_)| move_))
+            .collect::<Result<_>>()?)
+    }
+
+    fn query_pokemon(&self, query: &PokemonQuery) -> storage::Result<Vec<Container>> {
+        let (filter, mut values) = query_filter(query);
+        values.push(Value::Integer(query.limit.map_or(-1, |limit| limit as i64)));
+
+        // One query for the page of Pokémon and one for all of their moves, instead
+        // of a moves query per Pokémon.
+        let sql = format!(
+            "SELECT {} FROM pokemon WHERE {} ORDER BY id LIMIT ?",
+            POKEMON_COLUMNS, filter
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut containers = stmt
+            .query_map(params_from_iter(values.iter()), row_to_container)?
+            .collect::<Result<Vec<_>>>()?;
+        if containers.is_empty() {
+            return Ok(containers);
+        }
+
+        let index: HashMap<i64, usize> = containers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, container)| container.id.parse().ok().map(|id| (id, i)))
+            .collect();
+        let sql = format!(
+            "SELECT {} FROM moves
+             WHERE pokemon_id IN (SELECT id FROM pokemon WHERE {} ORDER BY id LIMIT ?)
+             ORDER BY pokemon_id, id",
+            MOVE_COLUMNS, filter
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let moves = stmt.query_map(params_from_iter(values.iter()), row_to_move)?;
+        for row in moves {
+            let (pokemon_id, move_) = row?;
+            if let Some(&i) = index.get(&pokemon_id) {
+                containers[i].learn_move(move_);
+            }
+        }
+
+        Ok(containers)
+    }
+
+    fn save_battle(
+        &mut self,
+        challenger: &mut Container,
+        opponent: &mut Container,
+        record: &BattleRecord,
+    ) -> storage::Result<String> {
+        let tx = write_transaction(&mut self.conn)?;
+        upsert_pokemon(&tx, challenger)?;
+        upsert_pokemon(&tx, opponent)?;
+        let battle_id = insert_battle(&tx, &self.trainer, record)?;
+        tx.commit()?;
+        Ok(battle_id.to_string())
+    }
+
+    fn recent_battles(&self, limit: usize) -> storage::Result<Vec<BattleRecord>> {
+        let sql = format!(
+            "SELECT {} FROM battle_records WHERE trainer = ?1 ORDER BY id DESC LIMIT ?2",
+            BATTLE_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let records = stmt.query_map(params![self.trainer, limit as i64], row_to_battle)?;
+        let mut records = records.collect::<Result<Vec<_>>>()?;
+        for record in &mut records {
+            self.load_battle_moves(record)?;
+        }
+        Ok(records)
+    }
+
+    fn load_battle(&self, id: &str) -> storage::Result<Option<BattleRecord>> {
+        let sql = format!(
+            "SELECT {} FROM battle_records WHERE id = ?1 AND trainer = ?2",
+            BATTLE_COLUMNS
+        );
+        let record = self
+            .conn
+            .query_row(&sql, params![id, self.trainer], row_to_battle)
+            .optional()?;
+        match record {
+            Some(mut record) => {
+                self.load_battle_moves(&mut record)?;
+                Ok(Some(record))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn load_trainer_stats(&self) -> storage::Result<TrainerStats> {
+        let mut stats = TrainerStats::new();
+        let counters = self
+            .conn
+            .query_row(
+                "SELECT total_pokemon_caught, total_pokemon_released, total_exp_gained, start_date
+                 FROM trainer_stats WHERE trainer = ?1",
+                params![self.trainer],
+                |row| {
+                    Ok((
+                        row.get::<_, u32>(0)?,
+                        row.get::<_, u32>(1)?,
+                        row.get::<_, u32>(2)?,
+                        row.get::<_, i64>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+        if let Some((caught, released, exp, start_date)) = counters {
+            stats.total_pokemon_caught = caught;
+            stats.total_pokemon_released = released;
+            stats.total_exp_gained = exp;
+            stats.start_date = DateTime::from_timestamp(start_date, 0).unwrap_or_default();
+        }
+
+        let (battles, wins): (u32, u32) = self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(winner_id = challenger_id), 0)
+             FROM battle_records WHERE trainer = ?1",
+            params![self.trainer],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        stats.total_battles = battles;
+        stats.total_wins = wins;
+        stats.total_losses = battles - wins;
+
+        if let Some(favorite) = self
+            .conn
+            .query_row(
+                "SELECT challenger_type FROM battle_records WHERE trainer = ?1
+                 GROUP BY challenger_type ORDER BY COUNT(*) DESC, challenger_type LIMIT 1",
+                params![self.trainer],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+        {
+            stats.favorite_pokemon_type = favorite;
+        }
+        if let Some(most_used) = self
+            .conn
+            .query_row(
+                "SELECT m.move_name FROM battle_moves m
+                 JOIN battle_records b ON m.battle_id = b.id AND m.pokemon_id = b.challenger_id
+                 WHERE b.trainer = ?1
+                 GROUP BY m.move_name ORDER BY SUM(m.uses) DESC, m.move_name LIMIT 1",
+                params![self.trainer],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+        {
+            stats.most_used_move = most_used;
+        }
+
+        stats.trainer = self.trainer.clone();
+        stats.inventory = self.load_inventory()?;
+        stats.battle_history = self.recent_battles(RECENT_BATTLES)?;
+        Ok(stats)
+    }
+
+    fn save_trainer_stats(&mut self, stats: &TrainerStats) -> storage::Result<()> {
+        self.conn.execute(
+            "INSERT INTO trainer_stats (trainer, total_pokemon_caught, total_pokemon_released, total_exp_gained, start_date)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(trainer) DO UPDATE SET
+                 total_pokemon_caught = excluded.total_pokemon_caught,
+                 total_pokemon_released = excluded.total_pokemon_released,
+                 total_exp_gained = excluded.total_exp_gained",
             params![
-                container.name,
-                container.level,
-                container.hp,
-                container.attack,
-                container.defense,
-                container.speed,
-                format!("{:?}", container.pokemon_type),
-                format!("{:?}", container.state),
-                container.exp,
-                container.exp_to_next_level,
-                container.namespace,
-                container.created_at.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+                self.trainer,
+                stats.total_pokemon_caught,
+                stats.total_pokemon_released,
+                stats.total_exp_gained,
+                stats.start_date.timestamp(),
             ],
         )?;
+        Ok(())
+    }
 
-        let pokemon_id = tx.last_insert_rowid();
+    fn load_challenges(&self, now: DateTime<Utc>) -> storage::Result<Vec<Challenge>> {
+        let sql = format!(
+            "SELECT {} FROM challenges WHERE trainer = ?1 AND expires_at > ?2 ORDER BY id",
+            CHALLENGE_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let challenges =
+            stmt.query_map(params![self.trainer, now.timestamp()], row_to_challenge)?;
+        Ok(challenges.collect::<Result<_>>()?)
+    }
 
-        for move_ in &container.moves {
+    fn save_challenges(&mut self, challenges: &[Challenge]) -> storage::Result<()> {
+        let tx = write_transaction(&mut self.conn)?;
+        for challenge in challenges {
             tx.execute(
-                "INSERT INTO moves (pokemon_id, name, pokemon_type, power, accuracy, pp, max_pp, description)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                "INSERT INTO challenges (id, trainer, challenge_type, description, target, progress,
+                     reward_exp, reward_items, completed, claimed, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 0, ?10, ?11)
+                 ON CONFLICT(trainer, id) DO UPDATE SET
+                     progress = excluded.progress,
+                     completed = excluded.completed",
                 params![
-                    pokemon_id,
-                    move_.name,
-                    format!("{:?}", move_.pokemon_type),
-                    move_.power,
-                    move_.accuracy,
-                    move_.pp,
-                    move_.max_pp,
-                    move_.description,
+                    challenge.id,
+                    self.trainer,
+                    format!("{:?}", challenge.challenge_type),
+                    challenge.description,
+                    challenge.target,
+                    challenge.progress,
+                    challenge.reward_exp,
+                    serde_json::to_string(&challenge.reward_items).unwrap_or_default(),
+                    challenge.completed,
+                    challenge.created_at.timestamp(),
+                    challenge.expires_at.timestamp(),
                 ],
             )?;
         }
+        Ok(tx.commit()?)
+    }
 
+    fn claim_challenge(&mut self, id: &str) -> storage::Result<ClaimOutcome> {
+        let tx = write_transaction(&mut self.conn)?;
+        let sql = format!(
+            "SELECT {} FROM challenges WHERE trainer = ?1 AND id = ?2",
+            CHALLENGE_COLUMNS
+        );
+        let challenge = match tx
+            .query_row(&sql, params![self.trainer, id], row_to_challenge)
+            .optional()?
+        {
+            None => return Ok(ClaimOutcome::NotFound),
+            Some(challenge) if !challenge.completed => return Ok(ClaimOutcome::NotCompleted),
+            Some(challenge) if challenge.claimed => return Ok(ClaimOutcome::AlreadyClaimed),
+            Some(challenge) => challenge,
+        };
+
+        // The claimed = 0 guard makes a concurrent second claim update nothing.
+        let updated = tx.execute(
+            "UPDATE challenges SET claimed = 1 WHERE trainer = ?1 AND id = ?2 AND claimed = 0",
+            params![self.trainer, id],
+        )?;
+        if updated == 0 {
+            return Ok(ClaimOutcome::AlreadyClaimed);
+        }
+        tx.execute(
+            "INSERT INTO trainer_stats (trainer, total_exp_gained, start_date) VALUES (?1, ?2, ?3)
+             ON CONFLICT(trainer) DO UPDATE SET
+                 total_exp_gained = total_exp_gained + excluded.total_exp_gained",
+            params![
+                self.trainer,
+                challenge.reward_exp,
+                Utc::now().timestamp()
+            ],
+        )?;
+        for item in &challenge.reward_items {
+            tx.execute(
+                "INSERT INTO inventory (trainer, item, quantity) VALUES (?1, ?2, 1)
+                 ON CONFLICT(trainer, item) DO UPDATE SET quantity = quantity + 1",
+                params![self.trainer, item],
+            )?;
+        }
         tx.commit()?;
-        println!("💾 Saved {} to database!", container.name);
-        Ok(())
+
+        Ok(ClaimOutcome::Claimed(Challenge {
+            claimed: true,
+            ..challenge
+        }))
     }
 
-    pub fn load_pokemon(&self, id: &str) -> Result<Option<Container>> {
+    fn load_teams(&self) -> storage::Result<Vec<(String, Vec<String>)>> {
+        let mut teams: Vec<(String, Vec<String>)> = Vec::new();
         let mut stmt = self.conn.prepare(
-            "SELECT name, level, hp, attack, defense, speed, pokemon_type, status, exp, exp_to_next_level, namespace, created_at
-             FROM pokemon WHERE id = ?1",
+            "SELECT t.name, m.pokemon_id FROM teams t
+             LEFT JOIN team_members m ON m.owner = t.owner AND m.team = t.name
+             WHERE t.owner = ?1
+             ORDER BY t.name, m.position",
         )?;
+        let rows = stmt.query_map(params![self.trainer], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
+        })?;
+        for row in rows {
+            let (name, member) = row?;
+            if teams.last().is_none_or(|(last, _)| *last != name) {
+                teams.push((name, Vec::new()));
+            }
+            if let (Some(member), Some((_, members))) = (member, teams.last_mut()) {
+                members.push(member.to_string());
+            }
+        }
+        Ok(teams)
+    }
 
-        let pokemon = stmt
-            .query_row(params![id], |row| {
-                let pokemon_type: String = row.get(6)?;
-                let _created_at =
-                    std::time::UNIX_EPOCH + std::time::Duration::from_secs(row.get(11)?);
-
-                let container = Container::new(
-                    &row.get::<_, String>(0)?,
-                    &row.get::<_, String>(10)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    row.get(4)?,
-                    row.get(5)?,
-                    match pokemon_type.as_str() {
-                        "Normal" => PokemonType::Normal,
-                        "Fire" => PokemonType::Fire,
-                        "Water" => PokemonType::Water,
-                        "Electric" => PokemonType::Electric,
-                        "Grass" => PokemonType::Grass,
-                        "Ice" => PokemonType::Ice,
-                        "Fighting" => PokemonType::Fighting,
-                        "Poison" => PokemonType::Poison,
-                        "Ground" => PokemonType::Ground,
-                        "Flying" => PokemonType::Flying,
-                        "Psychic" => PokemonType::Psychic,
-                        "Bug" => PokemonType::Bug,
-                        "Rock" => PokemonType::Rock,
-                        "Ghost" => PokemonType::Ghost,
-                        "Dragon" => PokemonType::Dragon,
-                        "Dark" => PokemonType::Dark,
-                        "Steel" => PokemonType::Steel,
-                        "Fairy" => PokemonType::Fairy,
-                        _ => PokemonType::Normal,
-                    },
-                );
-                Ok(container)
-            })
-            .optional()?;
+    fn save_team(&mut self, name: &str, members: &[String]) -> storage::Result<()> {
+        let tx = write_transaction(&mut self.conn)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO teams (owner, name, created_at) VALUES (?1, ?2, ?3)",
+            params![self.trainer, name, Utc::now().timestamp()],
+        )?;
+        tx.execute(
+            "DELETE FROM team_members WHERE owner = ?1 AND team = ?2",
+            params![self.trainer, name],
+        )?;
+        for (position, member) in members.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO team_members (owner, team, pokemon_id, position) VALUES (?1, ?2, ?3, ?4)",
+                params![self.trainer, name, member, position as i64],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
 
-        if let Some(mut container) = pokemon {
-            let mut stmt = self.conn.prepare(
-                "SELECT m.name, m.power, m.accuracy, m.pp, m.pokemon_type, m.description
-                 FROM moves m
-                 JOIN pokemon p ON m.pokemon_id = p.id
-                 WHERE p.id = ?1",
+    fn delete_team(&mut self, name: &str) -> storage::Result<bool> {
+        let tx = write_transaction(&mut self.conn)?;
+        tx.execute(
+            "DELETE FROM team_members WHERE owner = ?1 AND team = ?2",
+            params![self.trainer, name],
+        )?;
+        let deleted = tx.execute(
+            "DELETE FROM teams WHERE owner = ?1 AND name = ?2",
+            params![self.trainer, name],
+        )?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    fn begin_operation(&mut self, command: &str) -> storage::Result<()> {
+        Ok(journal::begin(&self.conn, &self.trainer, command)?)
+    }
+
+    fn end_operation(&mut self) -> storage::Result<()> {
+        Ok(journal::end(&mut self.conn, &self.trainer)?)
+    }
+
+    fn history(&self, limit: usize) -> storage::Result<Vec<JournalEntry>> {
+        Ok(journal::history(&self.conn, &self.trainer, limit)?)
+    }
+
+    fn undo(&mut self, count: usize) -> storage::Result<UndoOutcome> {
+        Ok(journal::undo(&mut self.conn, &self.trainer, count)?)
+    }
+
+    fn check_consistency(&self) -> storage::Result<Vec<Problem>> {
+        Ok(doctor::check(&self.conn)?)
+    }
+
+    fn repair_consistency(&mut self) -> storage::Result<Vec<Problem>> {
+        Ok(doctor::repair(&mut self.conn)?)
+    }
+
+    fn import_pokemon(
+        &mut self,
+        namespace: &str,
+        containers: &[Container],
+        on_conflict: ConflictPolicy,
+    ) -> storage::Result<ImportSummary> {
+        let tx = write_transaction(&mut self.conn)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO namespaces (name, owner) VALUES (?1, ?2)",
+            params![namespace, self.trainer],
+        )?;
+
+        let mut summary = ImportSummary::default();
+        for container in containers {
+            let mut container = container.clone();
+            container.namespace = namespace.to_string();
+            container
+                .labels
+                .insert("namespace".to_string(), namespace.to_string());
+
+            if let Some(existing) = find_pokemon_id(&tx, namespace, &container.name)? {
+                match on_conflict {
+                    ConflictPolicy::Skip => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    ConflictPolicy::Rename => {
+                        let mut suffix = 2;
+                        while find_pokemon_id(
+                            &tx,
+                            namespace,
+                            &format!("{}-{}", container.name, suffix),
+                        )?
+                        .is_some()
+                        {
+                            suffix += 1;
+                        }
+                        container.name = format!("{}-{}", container.name, suffix);
+                        summary.renamed += 1;
+                    }
+                    ConflictPolicy::Overwrite => {
+                        tx.execute("DELETE FROM moves WHERE pokemon_id = ?1", params![existing])?;
+                        tx.execute(
+                            "DELETE FROM team_members WHERE pokemon_id = ?1",
+                            params![existing],
+                        )?;
+                        tx.execute("DELETE FROM pokemon WHERE id = ?1", params![existing])?;
+                        summary.overwritten += 1;
+                    }
+                }
+            }
+
+            insert_pokemon(&tx, &container)?;
+            summary.imported += 1;
+        }
+
+        tx.commit()?;
+        Ok(summary)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub imported: u32,
+    pub skipped: u32,
+    pub renamed: u32,
+    pub overwritten: u32,
+}
+
+/// Sets up WAL mode, so readers don't block the writer, and a busy timeout, so
+/// concurrent writers wait for each other instead of failing straight away.
+fn configure_connection(conn: &Connection) -> Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+        row.get::<_, String>(0)
+    })?;
+    Ok(())
+}
+
+/// Starts a transaction that takes the write lock up front. A deferred transaction
+/// that reads before writing can't wait out a concurrent writer and would fail
+/// with SQLITE_BUSY despite the busy timeout.
+pub(crate) fn write_transaction(conn: &mut Connection) -> Result<Transaction<'_>> {
+    conn.transaction_with_behavior(TransactionBehavior::Immediate)
+}
+
+/// Inserts `container` if it has no storage id yet, or updates it otherwise. An
+/// update only applies if the stored version still matches `container.version`
+/// and the row hasn't been deleted since; otherwise it fails with
+/// `StorageError::Conflict`. On success the container carries its id and new
+/// version.
+fn upsert_pokemon(conn: &Connection, container: &mut Container) -> storage::Result<()> {
+    match container.id.parse::<i64>() {
+        Ok(id) => {
+            update_pokemon(conn, id, container)?;
+            container.version += 1;
+        }
+        Err(_) => {
+            container.id = insert_pokemon(conn, container)?.to_string();
+            container.version = 0;
+        }
+    }
+    Ok(())
+}
+
+fn insert_pokemon(conn: &Connection, container: &Container) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO pokemon (name, level, hp, attack, defense, speed, pokemon_type, status, exp, exp_to_next_level, namespace, created_at, max_hp, labels, stats, secondary_type, status_condition, sp_attack, sp_defense)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+        params![
+            container.name,
+            container.level,
+            container.hp,
+            container.attack,
+            container.defense,
+            container.speed,
+            format!("{:?}", container.pokemon_type),
+            format!("{:?}", container.state),
+            container.exp,
+            container.exp_to_next_level,
+            container.namespace,
+            container
+                .created_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            container.max_hp,
+            serde_json::to_string(&container.labels).unwrap_or_default(),
+            serde_json::to_string(&container.stats).unwrap_or_default(),
+            container.secondary_type.map(|t| format!("{:?}", t)),
+            container.status_condition.map(|s| format!("{:?}", s)),
+            container.sp_attack,
+            container.sp_defense,
+        ],
+    )?;
+
+    let pokemon_id = conn.last_insert_rowid();
+    insert_moves(conn, pokemon_id, &container.moves)?;
+    Ok(pokemon_id)
+}
+
+fn update_pokemon(conn: &Connection, id: i64, container: &Container) -> storage::Result<()> {
+    let updated = conn.execute(
+        "UPDATE pokemon SET name = ?2, level = ?3, hp = ?4, attack = ?5, defense = ?6, speed = ?7,
+             pokemon_type = ?8, status = ?9, exp = ?10, exp_to_next_level = ?11, namespace = ?12,
+             max_hp = ?13, labels = ?14, stats = ?15, secondary_type = ?17,
+             status_condition = ?18, sp_attack = ?19, sp_defense = ?20, version = version + 1
+         WHERE id = ?1 AND version = ?16",
+        params![
+            id,
+            container.name,
+            container.level,
+            container.hp,
+            container.attack,
+            container.defense,
+            container.speed,
+            format!("{:?}", container.pokemon_type),
+            format!("{:?}", container.state),
+            container.exp,
+            container.exp_to_next_level,
+            container.namespace,
+            container.max_hp,
+            serde_json::to_string(&container.labels).unwrap_or_default(),
+            serde_json::to_string(&container.stats).unwrap_or_default(),
+            container.version,
+            container.secondary_type.map(|t| format!("{:?}", t)),
+            container.status_condition.map(|s| format!("{:?}", s)),
+            container.sp_attack,
+            container.sp_defense,
+        ],
+    )?;
+    if updated == 0 {
+        return Err(StorageError::Conflict);
+    }
+    conn.execute("DELETE FROM moves WHERE pokemon_id = ?1", params![id])?;
+    Ok(insert_moves(conn, id, &container.moves)?)
+}
+
+fn insert_battle(conn: &Connection, trainer: &str, record: &BattleRecord) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO battle_records (challenger_id, opponent_id, winner_id, winner_name, loser_name,
+             challenger_type, opponent_type, turns, challenger_damage, opponent_damage, date, trainer,
+             seed, events)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            record.challenger_id,
+            record.opponent_id,
+            record.winner_id,
+            record.winner,
+            record.loser,
+            format!("{:?}", record.challenger_type),
+            format!("{:?}", record.opponent_type),
+            record.turns,
+            record.challenger_damage,
+            record.opponent_damage,
+            record.date.timestamp(),
+            trainer,
+            record.seed.map(|seed| seed as i64),
+            serde_json::to_string(&record.events).unwrap_or_default(),
+        ],
+    )?;
+    let battle_id = conn.last_insert_rowid();
+
+    for (pokemon_id, moves) in [
+        (&record.challenger_id, &record.challenger_moves),
+        (&record.opponent_id, &record.opponent_moves),
+    ] {
+        for (move_name, uses) in moves {
+            conn.execute(
+                "INSERT INTO battle_moves (battle_id, pokemon_id, move_name, uses)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![battle_id, pokemon_id, move_name, uses],
             )?;
+        }
+    }
+    Ok(battle_id)
+}
+
+fn insert_moves(conn: &Connection, pokemon_id: i64, moves: &[Move]) -> Result<()> {
+    for move_ in moves {
+        conn.execute(
+            "INSERT INTO moves (pokemon_id, name, pokemon_type, power, accuracy, pp, max_pp, description, effect, category)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                pokemon_id,
+                move_.name,
+                format!("{:?}", move_.pokemon_type),
+                move_.power,
+                move_.accuracy,
+                move_.pp,
+                move_.max_pp,
+                move_.description,
+                move_
+                    .effect
+                    .as_ref()
+                    .and_then(|effect| serde_json::to_string(effect).ok()),
+                move_.category.to_string(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+const POKEMON_COLUMNS: &str = "id, name, level, hp, attack, defense, speed, pokemon_type, status, exp, exp_to_next_level, namespace, created_at, max_hp, labels, stats, version, secondary_type, status_condition, sp_attack, sp_defense";
+
+/// Restricts a `pokemon` query to the namespaces of the trainer bound to `?2`.
+const OWNED_BY_TRAINER: &str = "namespace IN (SELECT name FROM namespaces WHERE owner = ?2)";
+
+const MOVE_COLUMNS: &str =
+    "pokemon_id, name, power, accuracy, pp, pokemon_type, description, max_pp, effect, category";
+
+fn parse_pokemon_type(pokemon_type: &str) -> PokemonType {
+    match pokemon_type {
+        "Normal" => PokemonType::Normal,
+        "Fire" => PokemonType::Fire,
+        "Water" => PokemonType::Water,
+        "Electric" => PokemonType::Electric,
+        "Grass" => PokemonType::Grass,
+        "Ice" => PokemonType::Ice,
+        "Fighting" => PokemonType::Fighting,
+        "Poison" => PokemonType::Poison,
+        "Ground" => PokemonType::Ground,
+        "Flying" => PokemonType::Flying,
+        "Psychic" => PokemonType::Psychic,
+        "Bug" => PokemonType::Bug,
+        "Rock" => PokemonType::Rock,
+        "Ghost" => PokemonType::Ghost,
+        "Dragon" => PokemonType::Dragon,
+        "Dark" => PokemonType::Dark,
+        "Steel" => PokemonType::Steel,
+        "Fairy" => PokemonType::Fairy,
+        _ => PokemonType::Normal,
+    }
+}
+
+/// The `battle_records` columns `row_to_battle` reads, in order.
+const BATTLE_COLUMNS: &str = "id, challenger_id, opponent_id, winner_id, winner_name, loser_name, challenger_type, opponent_type, turns, challenger_damage, opponent_damage, date, seed, events";
+
+/// Maps a row selected with `BATTLE_COLUMNS` to a battle record without its move counts.
+fn row_to_battle(row: &Row) -> Result<BattleRecord> {
+    Ok(BattleRecord {
+        id: row.get::<_, i64>(0)?.to_string(),
+        challenger_id: row.get::<_, i64>(1)?.to_string(),
+        opponent_id: row.get::<_, i64>(2)?.to_string(),
+        winner_id: row.get::<_, i64>(3)?.to_string(),
+        winner: row.get(4)?,
+        loser: row.get(5)?,
+        challenger_type: parse_pokemon_type(&row.get::<_, String>(6)?),
+        opponent_type: parse_pokemon_type(&row.get::<_, String>(7)?),
+        turns: row.get(8)?,
+        challenger_damage: row.get(9)?,
+        opponent_damage: row.get(10)?,
+        date: DateTime::from_timestamp(row.get(11)?, 0).unwrap_or_default(),
+        challenger_moves: HashMap::new(),
+        opponent_moves: HashMap::new(),
+        // SQLite integers are signed, so seeds are stored bit for bit as i64.
+        seed: row.get::<_, Option<i64>>(12)?.map(|seed| seed as u64),
+        events: row
+            .get::<_, Option<String>>(13)?
+            .and_then(|events| serde_json::from_str(&events).ok())
+            .unwrap_or_default(),
+    })
+}
+
+/// Maps a row selected with `POKEMON_COLUMNS` to a container without its moves.
+fn row_to_container(row: &Row) -> Result<Container> {
+    let id: i64 = row.get(0)?;
+    let pokemon_type: String = row.get(7)?;
+    let created_at = std::time::UNIX_EPOCH + std::time::Duration::from_secs(row.get(12)?);
+
+    let mut container = Container::new(
+        &row.get::<_, String>(1)?,
+        &row.get::<_, String>(11)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        parse_pokemon_type(&pokemon_type),
+    );
+    container.id = id.to_string();
+    container.created_at = created_at;
+    container.state = row
+        .get::<_, Option<String>>(8)?
+        .and_then(|state| state.parse().ok())
+        .unwrap_or(ContainerState::Created);
+    container.exp = row.get(9)?;
+    container.exp_to_next_level = row.get(10)?;
+    if let Some(max_hp) = row.get::<_, Option<i32>>(13)? {
+        container.max_hp = max_hp;
+    }
+    if let Some(labels) = row.get::<_, Option<String>>(14)? {
+        if let Ok(labels) = serde_json::from_str(&labels) {
+            container.labels = labels;
+        }
+    }
+    if let Some(stats) = row.get::<_, Option<String>>(15)? {
+        if let Ok(stats) = serde_json::from_str(&stats) {
+            container.stats = stats;
+        }
+    }
+    container.version = row.get(16)?;
+    container.secondary_type = row
+        .get::<_, Option<String>>(17)?
+        .map(|t| parse_pokemon_type(&t));
+    container.status_condition = row
+        .get::<_, Option<String>>(18)?
+        .and_then(|s| s.parse().ok());
+    if let Some(sp_attack) = row.get(19)? {
+        container.sp_attack = sp_attack;
+    }
+    if let Some(sp_defense) = row.get(20)? {
+        container.sp_defense = sp_defense;
+    }
+    Ok(container)
+}
+
+/// Maps a row selected with `MOVE_COLUMNS` to the owning Pokémon id and the move.
+fn row_to_move(row: &Row) -> Result<(i64, Move)> {
+    let pokemon_type: String = row.get(5)?;
+    let mut move_ = Move::new(
+        &row.get::<_, String>(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        parse_pokemon_type(&pokemon_type),
+        row.get::<_, Option<String>>(9)?
+            .and_then(|category| category.parse().ok())
+            .unwrap_or(crate::moves::MoveCategory::Physical),
+        &row.get::<_, String>(6)?,
+        row.get::<_, Option<String>>(8)?
+            .and_then(|effect| serde_json::from_str(&effect).ok()),
+    );
+    if let Some(max_pp) = row.get::<_, Option<u8>>(7)? {
+        move_.max_pp = max_pp.max(move_.pp);
+    }
+    Ok((row.get(0)?, move_))
+}
+
+const CHALLENGE_COLUMNS: &str = "id, challenge_type, description, target, progress, reward_exp, reward_items, completed, claimed, created_at, expires_at";
+
+/// Maps a row selected with `CHALLENGE_COLUMNS` to a challenge.
+fn row_to_challenge(row: &Row) -> Result<Challenge> {
+    let challenge_type: String = row.get(1)?;
+    let reward_items: String = row.get(6)?;
+    Ok(Challenge {
+        id: row.get(0)?,
+        challenge_type: challenge_type.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+        })?,
+        description: row.get(2)?,
+        target: row.get(3)?,
+        progress: row.get(4)?,
+        reward_exp: row.get(5)?,
+        reward_items: serde_json::from_str(&reward_items).unwrap_or_default(),
+        completed: row.get(7)?,
+        claimed: row.get(8)?,
+        created_at: DateTime::from_timestamp(row.get(9)?, 0).unwrap_or_default(),
+        expires_at: DateTime::from_timestamp(row.get(10)?, 0).unwrap_or_default(),
+    })
+}
+
+/// Creates any tables that don't exist yet. Columns added after a table was
+/// first released come from `MIGRATIONS`, which must run afterwards.
+fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS namespaces (
+            name TEXT PRIMARY KEY
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pokemon (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            level INTEGER,
+            hp INTEGER,
+            attack INTEGER,
+            defense INTEGER,
+            speed INTEGER,
+            pokemon_type TEXT,
+            status TEXT,
+            exp INTEGER,
+            exp_to_next_level INTEGER,
+            namespace TEXT,
+            created_at INTEGER,
+            FOREIGN KEY(namespace) REFERENCES namespaces(name)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS moves (
+            id INTEGER PRIMARY KEY,
+            pokemon_id INTEGER,
+            name TEXT NOT NULL,
+            pokemon_type TEXT,
+            power INTEGER,
+            accuracy INTEGER,
+            pp INTEGER,
+            max_pp INTEGER,
+            description TEXT,
+            FOREIGN KEY(pokemon_id) REFERENCES pokemon(id)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trainer_stats (
+            trainer TEXT PRIMARY KEY,
+            total_pokemon_caught INTEGER NOT NULL DEFAULT 0,
+            total_pokemon_released INTEGER NOT NULL DEFAULT 0,
+            total_exp_gained INTEGER NOT NULL DEFAULT 0,
+            start_date INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS battle_records (
+            id INTEGER PRIMARY KEY,
+            challenger_id INTEGER NOT NULL,
+            opponent_id INTEGER NOT NULL,
+            winner_id INTEGER NOT NULL,
+            winner_name TEXT NOT NULL,
+            loser_name TEXT NOT NULL,
+            challenger_type TEXT NOT NULL,
+            opponent_type TEXT NOT NULL,
+            turns INTEGER NOT NULL,
+            challenger_damage INTEGER NOT NULL,
+            opponent_damage INTEGER NOT NULL,
+            date INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS challenges (
+            id TEXT NOT NULL,
+            trainer TEXT NOT NULL,
+            challenge_type TEXT NOT NULL,
+            description TEXT NOT NULL,
+            target INTEGER NOT NULL,
+            progress INTEGER NOT NULL DEFAULT 0,
+            reward_exp INTEGER NOT NULL,
+            reward_items TEXT NOT NULL,
+            completed INTEGER NOT NULL DEFAULT 0,
+            claimed INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            PRIMARY KEY (trainer, id)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS battle_moves (
+            battle_id INTEGER NOT NULL,
+            pokemon_id INTEGER NOT NULL,
+            move_name TEXT NOT NULL,
+            uses INTEGER NOT NULL,
+            FOREIGN KEY(battle_id) REFERENCES battle_records(id)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trainers (
+            name TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS inventory (
+            trainer TEXT NOT NULL,
+            item TEXT NOT NULL,
+            quantity INTEGER NOT NULL,
+            PRIMARY KEY (trainer, item)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS teams (
+            name TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS team_members (
+            team TEXT NOT NULL,
+            pokemon_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (team, pokemon_id),
+            FOREIGN KEY(team) REFERENCES teams(name),
+            FOREIGN KEY(pokemon_id) REFERENCES pokemon(id)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS journal (
+            id INTEGER PRIMARY KEY,
+            trainer TEXT NOT NULL,
+            command TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            undone INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS journal_rows (
+            journal_id INTEGER NOT NULL,
+            table_name TEXT NOT NULL,
+            row_id INTEGER NOT NULL,
+            before TEXT,
+            PRIMARY KEY (journal_id, table_name, row_id),
+            FOREIGN KEY(journal_id) REFERENCES journal(id)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_journal_rows_row ON journal_rows (table_name, row_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn query_filter(query: &PokemonQuery) -> (String, Vec<Value>) {
+    let mut clauses = vec!["id > ?"];
+    let mut values = vec![Value::Integer(query.after_id.unwrap_or(0))];
+    if let Some(owner) = &query.owner {
+        clauses.push("namespace IN (SELECT name FROM namespaces WHERE owner = ?)");
+        values.push(Value::Text(owner.clone()));
+    }
+    if let Some(namespace) = &query.namespace {
+        clauses.push("namespace = ?");
+        values.push(Value::Text(namespace.clone()));
+    }
+    if let Some(pokemon_type) = query.pokemon_type {
+        clauses.push("(pokemon_type = ? OR secondary_type = ?)");
+        values.push(Value::Text(format!("{:?}", pokemon_type)));
+        values.push(Value::Text(format!("{:?}", pokemon_type)));
+    }
+    if let Some(min_level) = query.min_level {
+        clauses.push("level >= ?");
+        values.push(Value::Integer(min_level as i64));
+    }
+    (clauses.join(" AND "), values)
+}
+
+fn find_pokemon_id(conn: &Connection, namespace: &str, name: &str) -> Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM pokemon WHERE namespace = ?1 AND name = ?2",
+        params![namespace, name],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::Side;
+    use crate::challenge::{ChallengeManager, ChallengeType};
+    use crate::container::MAX_MOVES;
+    use crate::replay::BattleEvent;
+    use crate::storage::PokemonPages;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "oxidebox-{}-{}-{}",
+            name,
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn record_battle(db: &mut Database, record: &BattleRecord) {
+        let tx = write_transaction(&mut db.conn).unwrap();
+        insert_battle(&tx, &db.trainer, record).unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_backup_and_restore_round_trip() {
+        let dir = temp_dir("restore");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        db.create_namespace("kanto").unwrap();
+        db.backup_database(dir.join("snapshot.db")).unwrap();
+
+        db.create_namespace("johto").unwrap();
+        let rollback = db.restore_database(dir.join("snapshot.db")).unwrap();
+
+        assert_eq!(db.get_namespaces().unwrap(), vec!["kanto".to_string()]);
+        let previous = Database::open(rollback).unwrap();
+        assert_eq!(previous.get_namespaces().unwrap().len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
 
-            let moves = stmt.query_map(params![id], |row| {
-                let pokemon_type: String = row.get(4)?;
-                Ok(Move::new(
-                    &row.get::<_, String>(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    match pokemon_type.as_str() {
-                        "Normal" => PokemonType::Normal,
-                        "Fire" => PokemonType::Fire,
-                        "Water" => PokemonType::Water,
-                        "Electric" => PokemonType::Electric,
-                        "Grass" => PokemonType::Grass,
-                        "Ice" => PokemonType::Ice,
-                        "Fighting" => PokemonType::Fighting,
-                        "Poison" => PokemonType::Poison,
-                        "Ground" => PokemonType::Ground,
-                        "Flying" => PokemonType::Flying,
-                        "Psychic" => PokemonType::Psychic,
-                        "Bug" => PokemonType::Bug,
-                        "Rock" => PokemonType::Rock,
-                        "Ghost" => PokemonType::Ghost,
-                        "Dragon" => PokemonType::Dragon,
-                        "Dark" => PokemonType::Dark,
-                        "Steel" => PokemonType::Steel,
-                        "Fairy" => PokemonType::Fairy,
-                        _ => PokemonType::Normal,
+    #[test]
+    fn test_restore_rejects_foreign_database() {
+        let dir = temp_dir("foreign");
+        let foreign = dir.join("other.db");
+        Connection::open(&foreign)
+            .unwrap()
+            .execute("CREATE TABLE notes (body TEXT)", [])
+            .unwrap();
+
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        assert!(db.restore_database(&foreign).is_err());
+        assert!(!db.rollback_path().exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn seed(db: &mut Database, count: usize) {
+        db.create_namespace("kanto").unwrap();
+        db.create_namespace("johto").unwrap();
+        let tackle = Move::new(
+            "Tackle",
+            40,
+            100,
+            35,
+            PokemonType::Normal,
+            crate::moves::MoveCategory::Physical,
+            "A full-body charge",
+            None,
+        );
+        let tx = db.conn.transaction().unwrap();
+        for i in 0..count {
+            let namespace = if i % 2 == 0 { "kanto" } else { "johto" };
+            let pokemon_type = if i % 3 == 0 {
+                PokemonType::Fire
+            } else {
+                PokemonType::Water
+            };
+            let mut container = Container::new(
+                &format!("Pokemon{}", i),
+                namespace,
+                (i % 100) as u32 + 1,
+                50,
+                40,
+                40,
+                40,
+                pokemon_type,
+            );
+            if i % 5 == 0 {
+                container.secondary_type = Some(PokemonType::Flying);
+            }
+            container.learn_move(tackle.clone());
+            container.learn_move(tackle.clone());
+            insert_pokemon(&tx, &container).unwrap();
+        }
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn test_query_pokemon_filters_and_pages() {
+        let dir = temp_dir("query");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        seed(&mut db, 30);
+
+        let kanto = db.load_namespace_pokemon("kanto").unwrap();
+        assert_eq!(kanto.len(), 15);
+        assert!(kanto.iter().all(|c| c.moves.len() == 2));
+
+        let fire = db
+            .query_pokemon(&PokemonQuery {
+                pokemon_type: Some(PokemonType::Fire),
+                min_level: Some(10),
+                ..PokemonQuery::default()
+            })
+            .unwrap();
+        assert!(fire
+            .iter()
+            .all(|c| c.pokemon_type == PokemonType::Fire && c.level >= 10));
+
+        let flying = db
+            .query_pokemon(&PokemonQuery {
+                pokemon_type: Some(PokemonType::Flying),
+                ..PokemonQuery::default()
+            })
+            .unwrap();
+        assert_eq!(flying.len(), 6);
+        assert_eq!(flying[0].secondary_type, Some(PokemonType::Flying));
+
+        let pages: Vec<usize> = PokemonPages::new(&db, PokemonQuery::default(), 8)
+            .map(|page| page.unwrap().len())
+            .collect();
+        assert_eq!(pages, vec![8, 8, 8, 6]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture bench_list`.
+    #[test]
+    #[ignore]
+    fn bench_list_50k_rows() {
+        let dir = temp_dir("bench");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        let started = std::time::Instant::now();
+        seed(&mut db, 50_000);
+        println!("seeded 50k Pokémon in {:?}", started.elapsed());
+
+        let started = std::time::Instant::now();
+        let all = db.query_pokemon(&PokemonQuery::default()).unwrap();
+        assert_eq!(all.len(), 50_000);
+        println!("loaded 50k Pokémon in one batch in {:?}", started.elapsed());
+
+        let started = std::time::Instant::now();
+        let mut total = 0;
+        for page in PokemonPages::new(&db, PokemonQuery::default(), 500) {
+            total += page.unwrap().len();
+        }
+        assert_eq!(total, 50_000);
+        println!(
+            "streamed 50k Pokémon in pages of 500 in {:?}",
+            started.elapsed()
+        );
+
+        let started = std::time::Instant::now();
+        let kanto = db.load_namespace_pokemon("kanto").unwrap();
+        assert_eq!(kanto.len(), 25_000);
+        println!("loaded one namespace (25k) in {:?}", started.elapsed());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_trainer_stats_survive_reopen() {
+        let dir = temp_dir("trainer");
+        let path = dir.join("pokemon.db");
+        {
+            let mut db = Database::open(&path).unwrap();
+            let mut stats = db.load_trainer_stats().unwrap();
+            stats.total_pokemon_caught = 3;
+            db.save_trainer_stats(&stats).unwrap();
+
+            for (winner, moves) in [("1", 4), ("2", 1), ("1", 2)] {
+                record_battle(
+                    &mut db,
+                    &BattleRecord {
+                        id: String::new(),
+                        winner: "Charmander".to_string(),
+                        loser: "Squirtle".to_string(),
+                        date: Utc::now(),
+                        turns: 3,
+                        challenger_id: "1".to_string(),
+                        opponent_id: "2".to_string(),
+                        winner_id: winner.to_string(),
+                        challenger_type: PokemonType::Fire,
+                        opponent_type: PokemonType::Water,
+                        challenger_damage: 60,
+                        opponent_damage: 40,
+                        challenger_moves: HashMap::from([("Ember".to_string(), moves)]),
+                        opponent_moves: HashMap::from([("Bubble".to_string(), 10)]),
+                        seed: Some(u64::MAX),
+                        events: vec![
+                            BattleEvent::TurnStart { turn: 1 },
+                            BattleEvent::Faint { side: Side::Opponent },
+                        ],
                     },
-                    crate::moves::MoveCategory::Physical, // Default
-                    &row.get::<_, String>(5)?,
-                    None, // No effect info in DB
-                ))
-            })?;
-
-            for move_result in moves {
-                container.learn_move(move_result?);
+                );
             }
+        }
 
-            println!("📂 Loaded {} from database!", id);
-            Ok(Some(container))
-        } else {
-            Ok(None)
+        let stats = Database::open(&path).unwrap().load_trainer_stats().unwrap();
+        assert_eq!(stats.total_pokemon_caught, 3);
+        assert_eq!(stats.total_battles, 3);
+        assert_eq!(stats.total_wins, 2);
+        assert_eq!(stats.total_losses, 1);
+        assert_eq!(stats.favorite_pokemon_type, "Fire");
+        assert_eq!(stats.most_used_move, "Ember");
+        assert_eq!(stats.battle_history.len(), 3);
+        assert_eq!(stats.battle_history[0].challenger_moves["Ember"], 2);
+        assert_eq!(stats.battle_history[0].seed, Some(u64::MAX));
+
+        let mut db = Database::open(&path).unwrap();
+        let battle = db.load_battle("2").unwrap().unwrap();
+        assert_eq!(battle.winner_id, "2");
+        assert_eq!(battle.opponent_moves["Bubble"], 10);
+        assert_eq!(battle.events.len(), 2);
+        assert!(db.load_battle("4").unwrap().is_none());
+        db.create_trainer("misty").unwrap();
+        db.set_trainer("misty");
+        assert!(db.load_battle("2").unwrap().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_challenge_reward_is_claimed_once() {
+        let dir = temp_dir("claim");
+        let path = dir.join("pokemon.db");
+        let id = {
+            let mut db = Database::open(&path).unwrap();
+            let mut manager = ChallengeManager::load(&mut db).unwrap();
+            manager.update_challenge_progress(ChallengeType::CatchPokemon, 2);
+            manager.save(&mut db).unwrap();
+            manager
+                .active_challenges
+                .values()
+                .find(|c| c.challenge_type == ChallengeType::CatchPokemon)
+                .unwrap()
+                .id
+                .clone()
+        };
+
+        // A later invocation sees the same ids and progress.
+        let mut db = Database::open(&path).unwrap();
+        let manager = ChallengeManager::load(&mut db).unwrap();
+        assert!(manager.active_challenges[&id].completed);
+
+        assert!(matches!(
+            db.claim_challenge(&id).unwrap(),
+            ClaimOutcome::Claimed(_)
+        ));
+        assert!(matches!(
+            db.claim_challenge(&id).unwrap(),
+            ClaimOutcome::AlreadyClaimed
+        ));
+        assert!(matches!(
+            db.claim_challenge("daily-0-1").unwrap(),
+            ClaimOutcome::NotFound
+        ));
+        assert_eq!(db.load_trainer_stats().unwrap().total_exp_gained, 300);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_team_members_keep_order_and_follow_deletes() {
+        let dir = temp_dir("teams");
+        let path = dir.join("pokemon.db");
+        let ids: Vec<String> = {
+            let mut db = Database::open(&path).unwrap();
+            db.create_namespace("kanto").unwrap();
+            let ids: Vec<String> = ["Pikachu", "Onix", "Starmie"]
+                .iter()
+                .map(|name| {
+                    let mut container =
+                        Container::new(name, "kanto", 10, 40, 30, 30, 30, PokemonType::Normal);
+                    db.save_pokemon(&mut container).unwrap()
+                })
+                .collect();
+            db.save_team("empty", &[]).unwrap();
+            db.save_team("red", &[ids[2].clone(), ids[0].clone(), ids[1].clone()])
+                .unwrap();
+            ids
+        };
+
+        let mut db = Database::open(&path).unwrap();
+        db.delete_pokemon(&ids[0]).unwrap();
+        assert_eq!(
+            db.load_teams().unwrap(),
+            vec![
+                ("empty".to_string(), vec![]),
+                ("red".to_string(), vec![ids[2].clone(), ids[1].clone()]),
+            ]
+        );
+        assert!(db.delete_team("red").unwrap());
+        assert!(!db.delete_team("red").unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_trainers_have_separate_progression() {
+        let dir = temp_dir("trainers");
+        let path = dir.join("pokemon.db");
+        {
+            let mut db = Database::open(&path).unwrap();
+            db.create_namespace("kanto").unwrap();
+            let mut pikachu =
+                Container::new("Pikachu", "kanto", 5, 35, 55, 40, 90, PokemonType::Electric);
+            let pikachu = db.save_pokemon(&mut pikachu).unwrap();
+            db.save_team("red", std::slice::from_ref(&pikachu)).unwrap();
+            assert!(db.create_trainer("misty").unwrap());
+            assert!(!db.create_trainer("misty").unwrap());
+            assert!(db.switch_trainer("misty").unwrap());
+            assert!(!db.switch_trainer("gary").unwrap());
+
+            assert!(!db.create_namespace("kanto").unwrap());
+            assert!(!db.delete_namespace("kanto").unwrap());
+            assert!(db.load_pokemon(&pikachu).unwrap().is_none());
+            assert!(!db.delete_pokemon(&pikachu).unwrap());
+            assert!(db.load_teams().unwrap().is_empty());
+            assert!(!db.delete_team("red").unwrap());
+            // Misty can have a team of the same name without touching default's.
+            db.save_team("red", &[]).unwrap();
+            assert_eq!(db.load_teams().unwrap(), vec![("red".to_string(), Vec::new())]);
+            db.create_namespace("cerulean").unwrap();
+
+            let mut manager = ChallengeManager::load(&mut db).unwrap();
+            manager.update_challenge_progress(ChallengeType::CatchPokemon, 2);
+            manager.save(&mut db).unwrap();
+            let id = manager
+                .active_challenges
+                .values()
+                .find(|c| c.challenge_type == ChallengeType::CatchPokemon)
+                .unwrap()
+                .id
+                .clone();
+            assert!(matches!(
+                db.claim_challenge(&id).unwrap(),
+                ClaimOutcome::Claimed(_)
+            ));
         }
+
+        // The switch sticks for later sessions.
+        let mut db = Database::open(&path).unwrap();
+        assert_eq!(db.trainer(), "misty");
+        assert_eq!(db.list_trainers().unwrap(), vec!["default", "misty"]);
+        assert_eq!(db.get_namespaces().unwrap(), vec!["cerulean"]);
+        let misty = db.load_trainer_stats().unwrap();
+        assert_eq!(misty.total_exp_gained, 300);
+        assert_eq!(
+            misty.inventory,
+            vec![("Great Ball".to_string(), 1), ("Poké Ball".to_string(), 1)]
+        );
+
+        db.set_trainer(DEFAULT_TRAINER);
+        assert_eq!(db.get_namespaces().unwrap(), vec!["kanto"]);
+        assert!(db.load_pokemon("1").unwrap().is_some());
+        assert_eq!(
+            db.load_teams().unwrap(),
+            vec![("red".to_string(), vec!["1".to_string()])]
+        );
+        let default = db.load_trainer_stats().unwrap();
+        assert_eq!(default.total_exp_gained, 0);
+        assert!(default.inventory.is_empty());
+        assert!(db.load_challenges(Utc::now()).unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_stale_save_is_a_conflict() {
+        let dir = temp_dir("stale");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        let journal_mode: String = db
+            .conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        db.create_namespace("kanto").unwrap();
+        let mut pikachu =
+            Container::new("Pikachu", "kanto", 5, 35, 55, 40, 90, PokemonType::Electric);
+        let id = db.save_pokemon(&mut pikachu).unwrap();
+
+        let mut first = db.load_pokemon(&id).unwrap().unwrap();
+        let mut second = db.load_pokemon(&id).unwrap().unwrap();
+        first.exp = 10;
+        db.save_pokemon(&mut first).unwrap();
+        assert_eq!(first.version, 1);
+
+        second.exp = 20;
+        let err = db.save_pokemon(&mut second).unwrap_err();
+        assert!(matches!(err, StorageError::Conflict));
+        assert_eq!(db.load_pokemon(&id).unwrap().unwrap().exp, 10);
+
+        // A copy of a Pokémon released in the meantime doesn't come back.
+        let mut released = db.load_pokemon(&id).unwrap().unwrap();
+        db.delete_pokemon(&id).unwrap();
+        let err = db.save_pokemon(&mut released).unwrap_err();
+        assert!(matches!(err, StorageError::Conflict));
+        assert_eq!(released.id, id);
+        let count: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM pokemon", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        fs::remove_dir_all(dir).unwrap();
     }
 
-    pub fn load_all_pokemon(&self) -> Result<Vec<Container>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare("SELECT id FROM pokemon")?;
-        let ids = stmt.query_map([], |row| row.get::<_, i64>(0))?;
-        let mut containers = Vec::new();
-        for id_result in ids {
-            let id: i64 = id_result?;
-            let id_str = id.to_string();
-            if let Some(container) = self.load_pokemon(&id_str)? {
-                containers.push(container);
+    #[test]
+    fn test_parallel_writers_do_not_lose_updates() {
+        const WRITERS: u32 = 4;
+        const UPDATES: u32 = 25;
+
+        let dir = temp_dir("parallel");
+        let path = dir.join("pokemon.db");
+        let id = {
+            let mut db = Database::open(&path).unwrap();
+            db.create_namespace("kanto").unwrap();
+            let mut onix = Container::new("Onix", "kanto", 5, 35, 45, 160, 70, PokemonType::Rock);
+            db.save_pokemon(&mut onix).unwrap()
+        };
+
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|_| {
+                let path = path.clone();
+                let id = id.clone();
+                std::thread::spawn(move || {
+                    let mut db = Database::open(&path).unwrap();
+                    let mut conflicts = 0;
+                    for _ in 0..UPDATES {
+                        loop {
+                            let mut onix = db.load_pokemon(&id).unwrap().unwrap();
+                            onix.exp += 1;
+                            match db.save_pokemon(&mut onix) {
+                                Ok(_) => break,
+                                Err(StorageError::Conflict) => conflicts += 1,
+                                Err(e) => panic!("writer failed: {}", e),
+                            }
+                        }
+                    }
+                    conflicts
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let onix = Database::open(&path)
+            .unwrap()
+            .load_pokemon(&id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(onix.exp, WRITERS * UPDATES);
+        assert_eq!(onix.version, WRITERS * UPDATES);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_auto_backup_rotation() {
+        let dir = temp_dir("rotate");
+        let db = Database::open(dir.join("pokemon.db")).unwrap();
+        assert_eq!(db.auto_backup().unwrap(), None);
+
+        db.set_auto_backup_limit(2).unwrap();
+        for _ in 0..4 {
+            assert!(db.auto_backup().unwrap().is_some());
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        assert_eq!(fs::read_dir(dir.join(BACKUP_DIR)).unwrap().count(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn journaled<T>(db: &mut Database, command: &str, op: impl FnOnce(&mut Database) -> T) -> T {
+        db.begin_operation(command).unwrap();
+        let result = op(db);
+        db.end_operation().unwrap();
+        result
+    }
+
+    #[test]
+    fn test_undo_brings_back_a_released_pokemon() {
+        let dir = temp_dir("undo-release");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        db.create_namespace("kanto").unwrap();
+        let mut pikachu = Container::new("Pikachu", "kanto", 5, 35, 55, 40, 90, PokemonType::Electric);
+        pikachu.learn_move(Move::new(
+            "Thunder Shock",
+            40,
+            100,
+            30,
+            PokemonType::Electric,
+            crate::moves::MoveCategory::Special,
+            "A jolt of electricity",
+            None,
+        ));
+        let id = db.save_pokemon(&mut pikachu).unwrap();
+        db.save_team("red", std::slice::from_ref(&id)).unwrap();
+
+        journaled(&mut db, "release 1", |db| db.delete_pokemon(&id).unwrap());
+        // Commands that change nothing leave no entry behind.
+        journaled(&mut db, "start 99", |_| ());
+        assert!(db.load_pokemon(&id).unwrap().is_none());
+
+        match db.undo(1).unwrap() {
+            UndoOutcome::Undone(entries) => assert_eq!(entries[0].command, "release 1"),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        let restored = db.load_pokemon(&id).unwrap().unwrap();
+        assert_eq!(restored.moves.len(), 1);
+        assert_eq!(db.load_teams().unwrap(), vec![("red".to_string(), vec![id])]);
+
+        let history = db.history(10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].undone);
+        assert!(matches!(db.undo(1).unwrap(), UndoOutcome::NothingToUndo));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_undo_refuses_rows_changed_later_and_rolls_back() {
+        let dir = temp_dir("undo-conflict");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        db.create_namespace("kanto").unwrap();
+        db.create_trainer("misty").unwrap();
+        let mut eevee = Container::new("Eevee", "kanto", 5, 55, 55, 50, 55, PokemonType::Normal);
+        let id = db.save_pokemon(&mut eevee).unwrap();
+
+        journaled(&mut db, "battle 1 2", |db| {
+            eevee.exp = 100;
+            db.save_pokemon(&mut eevee).unwrap();
+        });
+        journaled(&mut db, "create-namespace johto", |db| {
+            db.create_namespace("johto").unwrap();
+        });
+        db.set_trainer("misty");
+        journaled(&mut db, "battle 1 3", |db| {
+            eevee.exp = 250;
+            db.save_pokemon(&mut eevee).unwrap();
+        });
+        db.set_trainer(DEFAULT_TRAINER);
+
+        // The namespace is undone first, then the battle conflicts with misty's,
+        // so neither change is rolled back.
+        match db.undo(2).unwrap() {
+            UndoOutcome::Conflict { entry, later } => {
+                assert_eq!(entry.command, "battle 1 2");
+                assert_eq!(later.command, "battle 1 3");
             }
+            other => panic!("unexpected outcome: {:?}", other),
         }
-        Ok(containers)
+        assert_eq!(db.load_pokemon(&id).unwrap().unwrap().exp, 250);
+        assert!(db.get_namespaces().unwrap().contains(&"johto".to_string()));
+        assert!(db.history(10).unwrap().iter().all(|entry| !entry.undone));
+
+        db.set_trainer("misty");
+        assert!(matches!(db.undo(1).unwrap(), UndoOutcome::Undone(_)));
+        db.set_trainer(DEFAULT_TRAINER);
+        assert!(matches!(db.undo(2).unwrap(), UndoOutcome::Undone(_)));
+        let eevee = db.load_pokemon(&id).unwrap().unwrap();
+        assert_eq!(eevee.exp, 0);
+        // Undo counts as a change, so stale copies can't overwrite it.
+        assert_eq!(eevee.version, 4);
+        assert_eq!(db.get_namespaces().unwrap(), vec!["kanto".to_string()]);
+        fs::remove_dir_all(dir).unwrap();
     }
 
-    // pub fn backup_database(&self, backup_path: &str) -> Result<()> {
-    //     let backup_conn = Connection::open(backup_path)?;
-    //     self.conn.backup(DatabaseName::Main, &backup_conn, None)?;
-    //     Ok(())
-    // }
+    #[test]
+    fn test_doctor_reports_and_repairs_inconsistencies() {
+        let dir = temp_dir("doctor");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        seed(&mut db, 3);
+        assert!(db.check_consistency().unwrap().is_empty());
 
-    #[allow(dead_code)]
-    pub fn migrate_database(&mut self) -> Result<()> {
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS migrations (
-                version INTEGER PRIMARY KEY,
-                applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
-        Ok(())
+        db.conn.pragma_update(None, "foreign_keys", false).unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO moves (pokemon_id, name, pokemon_type, power, accuracy, pp, description)
+                 VALUES (99, 'Ember', 'Fire', 40, 100, 25, '');
+                 INSERT INTO teams (name, created_at) VALUES ('red', 0);
+                 INSERT INTO team_members (team, pokemon_id, position) VALUES ('red', 98, 0);
+                 UPDATE pokemon SET namespace = 'hoenn' WHERE id = 1;
+                 UPDATE pokemon SET pokemon_type = 'fire', secondary_type = 'flying',
+                     status_condition = 'burn' WHERE id = 2;
+                 UPDATE pokemon SET pokemon_type = 'Plasma', hp = 80, max_hp = 50 WHERE id = 3;
+                 INSERT INTO moves (pokemon_id, name, pokemon_type, power, accuracy, pp, description)
+                 SELECT pokemon_id, name, pokemon_type, power, accuracy, pp, description
+                 FROM moves WHERE pokemon_id = 3 UNION ALL
+                 SELECT pokemon_id, name, pokemon_type, power, accuracy, pp, description
+                 FROM moves WHERE pokemon_id = 3 AND id = (SELECT MIN(id) FROM moves WHERE pokemon_id = 3);",
+            )
+            .unwrap();
+        db.conn.pragma_update(None, "foreign_keys", true).unwrap();
+
+        let problems = db.check_consistency().unwrap();
+        assert_eq!(problems.len(), 9);
+        assert!(problems.contains(&Problem::TooManyMoves {
+            pokemon_id: 3,
+            count: 5
+        }));
+        assert_eq!(db.repair_consistency().unwrap(), problems);
+        assert!(db.check_consistency().unwrap().is_empty());
+
+        assert!(db.namespace_owner("hoenn").unwrap().is_some());
+        let second = db.load_pokemon("2").unwrap().unwrap();
+        assert_eq!(second.type_name(), "Fire/Flying");
+        assert_eq!(
+            second.status_condition,
+            Some(crate::moves::StatusCondition::Burn)
+        );
+        let third = db.load_pokemon("3").unwrap().unwrap();
+        assert_eq!((third.hp, third.max_hp), (50, 50));
+        assert_eq!(third.moves.len(), MAX_MOVES);
+        assert_eq!(db.load_teams().unwrap(), vec![("red".to_string(), Vec::new())]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_doctor_repairs_can_be_undone() {
+        let dir = temp_dir("doctor-undo");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        seed(&mut db, 1);
+        db.conn
+            .execute("UPDATE pokemon SET hp = 999 WHERE id = 1", [])
+            .unwrap();
+        let problems = db.check_consistency().unwrap();
+        assert_eq!(problems.len(), 1);
+
+        journaled(&mut db, "doctor --fix", |db| db.repair_consistency().unwrap());
+        assert_eq!(db.load_pokemon("1").unwrap().unwrap().hp, 50);
+        assert!(matches!(db.undo(1).unwrap(), UndoOutcome::Undone(_)));
+        assert_eq!(db.check_consistency().unwrap(), problems);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_spent_pp_is_kept_separately_from_max_pp() {
+        let dir = temp_dir("pp");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        seed(&mut db, 1);
+        let mut pokemon = db.load_pokemon("1").unwrap().unwrap();
+        pokemon.moves[0].pp = 3;
+        db.save_pokemon(&mut pokemon).unwrap();
+
+        let stored = db.load_pokemon("1").unwrap().unwrap();
+        assert_eq!((stored.moves[0].pp, stored.moves[0].max_pp), (3, 35));
+        assert_eq!((stored.moves[1].pp, stored.moves[1].max_pp), (35, 35));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_status_condition_and_move_effects_are_kept() {
+        use crate::moves::{EffectType, MoveEffect, StatusCondition};
+
+        let dir = temp_dir("status");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        seed(&mut db, 1);
+        let mut pokemon = db.load_pokemon("1").unwrap().unwrap();
+        assert_eq!(pokemon.status_condition, None);
+        assert!(pokemon.moves[0].effect.is_none());
+        pokemon.status_condition = Some(StatusCondition::Paralysis);
+        pokemon.moves[1].effect = Some(MoveEffect {
+            effect_type: EffectType::StatusCondition(StatusCondition::Burn),
+            chance: 10,
+            turns: None,
+        });
+        db.save_pokemon(&mut pokemon).unwrap();
+
+        let stored = db.load_pokemon("1").unwrap().unwrap();
+        assert_eq!(stored.status_condition, Some(StatusCondition::Paralysis));
+        assert!(stored.moves[0].effect.is_none());
+        let effect = stored.moves[1].effect.as_ref().unwrap();
+        assert!(matches!(
+            effect.effect_type,
+            EffectType::StatusCondition(StatusCondition::Burn)
+        ));
+        assert_eq!(effect.chance, 10);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_special_stats_and_move_categories_are_kept() {
+        use crate::moves::MoveCategory;
+
+        let dir = temp_dir("special");
+        let mut db = Database::open(dir.join("pokemon.db")).unwrap();
+        seed(&mut db, 1);
+        let mut pokemon = db.load_pokemon("1").unwrap().unwrap();
+        assert_eq!(pokemon.moves[0].category, MoveCategory::Physical);
+        pokemon.sp_attack = 95;
+        pokemon.sp_defense = 85;
+        pokemon.moves[1].category = MoveCategory::Special;
+        db.save_pokemon(&mut pokemon).unwrap();
+
+        let stored = db.load_pokemon("1").unwrap().unwrap();
+        assert_eq!((stored.sp_attack, stored.sp_defense), (95, 85));
+        assert_eq!(stored.moves[0].category, MoveCategory::Physical);
+        assert_eq!(stored.moves[1].category, MoveCategory::Special);
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,356 @@
+use crate::container::MAX_MOVES;
+use crate::database::write_transaction;
+use crate::moves::{PokemonType, StatusCondition};
+use crate::storage::DEFAULT_TRAINER;
+use rusqlite::{params, Connection, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// An inconsistency found by `check`. Each one can be repaired on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// A move whose Pokémon no longer exists.
+    OrphanMove {
+        move_id: i64,
+        pokemon_id: Option<i64>,
+    },
+    /// A team member whose Pokémon no longer exists.
+    OrphanTeamMember {
+        team: String,
+        pokemon_id: i64,
+    },
+    /// A Pokémon in a namespace missing from `namespaces`.
+    MissingNamespace {
+        pokemon_id: i64,
+        namespace: String,
+    },
+    /// A type that isn't one of `PokemonType`'s names, which loading reads as Normal.
+    UnknownType {
+        table: &'static str,
+        row_id: i64,
+        value: Option<String>,
+    },
+    /// A secondary type that isn't one of `PokemonType`'s names.
+    UnknownSecondaryType {
+        pokemon_id: i64,
+        value: String,
+    },
+    /// A status condition that isn't one of `StatusCondition`'s names, which
+    /// loading reads as no status.
+    UnknownStatusCondition {
+        pokemon_id: i64,
+        value: String,
+    },
+    HpAboveMax {
+        pokemon_id: i64,
+        hp: i64,
+        max_hp: i64,
+    },
+    TooManyMoves {
+        pokemon_id: i64,
+        count: i64,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::OrphanMove {
+                move_id,
+                pokemon_id: Some(pokemon_id),
+            } => write!(
+                f,
+                "move {} belongs to missing Pokémon {}",
+                move_id, pokemon_id
+            ),
+            Problem::OrphanMove {
+                move_id,
+                pokemon_id: None,
+            } => write!(f, "move {} has no Pokémon", move_id),
+            Problem::OrphanTeamMember { team, pokemon_id } => {
+                write!(f, "team {} lists missing Pokémon {}", team, pokemon_id)
+            }
+            Problem::MissingNamespace {
+                pokemon_id,
+                namespace,
+            } => write!(
+                f,
+                "Pokémon {} is in namespace {}, which doesn't exist",
+                pokemon_id, namespace
+            ),
+            Problem::UnknownType {
+                table,
+                row_id,
+                value,
+            } => write!(
+                f,
+                "{} row {} has unknown type {}",
+                table,
+                row_id,
+                value.as_deref().unwrap_or("NULL")
+            ),
+            Problem::UnknownSecondaryType { pokemon_id, value } => write!(
+                f,
+                "Pokémon {} has unknown secondary type {}",
+                pokemon_id, value
+            ),
+            Problem::UnknownStatusCondition { pokemon_id, value } => write!(
+                f,
+                "Pokémon {} has unknown status condition {}",
+                pokemon_id, value
+            ),
+            Problem::HpAboveMax {
+                pokemon_id,
+                hp,
+                max_hp,
+            } => write!(
+                f,
+                "Pokémon {} has {} HP but a maximum of {}",
+                pokemon_id, hp, max_hp
+            ),
+            Problem::TooManyMoves { pokemon_id, count } => write!(
+                f,
+                "Pokémon {} knows {} moves (at most {})",
+                pokemon_id, count, MAX_MOVES
+            ),
+        }
+    }
+}
+
+impl Problem {
+    /// What `repair` does about the problem.
+    pub fn fix_description(&self) -> String {
+        match self {
+            Problem::OrphanMove { .. } => "delete the move".to_string(),
+            Problem::OrphanTeamMember { .. } => "remove it from the team".to_string(),
+            Problem::MissingNamespace { namespace, .. } => format!(
+                "create namespace {} for trainer {}",
+                namespace, DEFAULT_TRAINER
+            ),
+            Problem::UnknownType { value, .. } => {
+                format!("set the type to {}", repaired_type(value))
+            }
+            Problem::UnknownSecondaryType { value, .. } => match PokemonType::from_str(value) {
+                Ok(secondary) => format!("set the secondary type to {}", secondary),
+                Err(_) => "remove the secondary type".to_string(),
+            },
+            Problem::UnknownStatusCondition { value, .. } => {
+                match StatusCondition::from_str(value) {
+                    Ok(status) => format!("set the status condition to {}", status),
+                    Err(_) => "remove the status condition".to_string(),
+                }
+            }
+            Problem::HpAboveMax { max_hp, .. } => format!("set HP to {}", max_hp),
+            Problem::TooManyMoves { .. } => format!("keep the first {} moves", MAX_MOVES),
+        }
+    }
+}
+
+/// The stored spelling of a type: the one `PokemonType` displays as. Differently
+/// cased names are kept, anything else becomes Normal as it does when loading.
+fn repaired_type(value: &Option<String>) -> PokemonType {
+    value
+        .as_deref()
+        .and_then(|value| PokemonType::from_str(value).ok())
+        .unwrap_or(PokemonType::Normal)
+}
+
+/// Scans the database for inconsistencies without changing anything.
+pub fn check(conn: &Connection) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.pokemon_id FROM moves m
+         WHERE NOT EXISTS (SELECT 1 FROM pokemon p WHERE p.id = m.pokemon_id)
+         ORDER BY m.id",
+    )?;
+    for problem in stmt.query_map([], |row| {
+        Ok(Problem::OrphanMove {
+            move_id: row.get(0)?,
+            pokemon_id: row.get(1)?,
+        })
+    })? {
+        problems.push(problem?);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT t.team, t.pokemon_id FROM team_members t
+         WHERE NOT EXISTS (SELECT 1 FROM pokemon p WHERE p.id = t.pokemon_id)
+         ORDER BY t.team, t.position",
+    )?;
+    for problem in stmt.query_map([], |row| {
+        Ok(Problem::OrphanTeamMember {
+            team: row.get(0)?,
+            pokemon_id: row.get(1)?,
+        })
+    })? {
+        problems.push(problem?);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.namespace FROM pokemon p
+         WHERE p.namespace IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM namespaces n WHERE n.name = p.namespace)
+         ORDER BY p.id",
+    )?;
+    for problem in stmt.query_map([], |row| {
+        Ok(Problem::MissingNamespace {
+            pokemon_id: row.get(0)?,
+            namespace: row.get(1)?,
+        })
+    })? {
+        problems.push(problem?);
+    }
+
+    let known: Vec<String> = PokemonType::all_types()
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+    for table in ["pokemon", "moves"] {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, pokemon_type FROM {} ORDER BY id",
+            table
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        for row in rows {
+            let (row_id, value) = row?;
+            if !value.as_ref().is_some_and(|value| known.contains(value)) {
+                problems.push(Problem::UnknownType {
+                    table,
+                    row_id,
+                    value,
+                });
+            }
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT id, secondary_type FROM pokemon WHERE secondary_type IS NOT NULL ORDER BY id",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?)))?;
+    for row in rows {
+        let (pokemon_id, value) = row?;
+        if !known.contains(&value) {
+            problems.push(Problem::UnknownSecondaryType { pokemon_id, value });
+        }
+    }
+
+    let statuses: Vec<String> = StatusCondition::all()
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let mut stmt = conn.prepare(
+        "SELECT id, status_condition FROM pokemon WHERE status_condition IS NOT NULL ORDER BY id",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?)))?;
+    for row in rows {
+        let (pokemon_id, value) = row?;
+        if !statuses.contains(&value) {
+            problems.push(Problem::UnknownStatusCondition { pokemon_id, value });
+        }
+    }
+
+    let mut stmt =
+        conn.prepare("SELECT id, hp, max_hp FROM pokemon WHERE hp > max_hp ORDER BY id")?;
+    for problem in stmt.query_map([], |row| {
+        Ok(Problem::HpAboveMax {
+            pokemon_id: row.get(0)?,
+            hp: row.get(1)?,
+            max_hp: row.get(2)?,
+        })
+    })? {
+        problems.push(problem?);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT pokemon_id, COUNT(*) FROM moves
+         WHERE pokemon_id IN (SELECT id FROM pokemon)
+         GROUP BY pokemon_id HAVING COUNT(*) > ?1
+         ORDER BY pokemon_id",
+    )?;
+    for problem in stmt.query_map(params![MAX_MOVES as i64], |row| {
+        Ok(Problem::TooManyMoves {
+            pokemon_id: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })? {
+        problems.push(problem?);
+    }
+
+    Ok(problems)
+}
+
+/// Repairs every problem `check` finds in one transaction and returns them.
+pub fn repair(conn: &mut Connection) -> Result<Vec<Problem>> {
+    let tx = write_transaction(conn)?;
+    let problems = check(&tx)?;
+    for problem in &problems {
+        match problem {
+            Problem::OrphanMove { move_id, .. } => {
+                tx.execute("DELETE FROM moves WHERE id = ?1", params![move_id])?;
+            }
+            Problem::OrphanTeamMember { team, pokemon_id } => {
+                tx.execute(
+                    "DELETE FROM team_members WHERE team = ?1 AND pokemon_id = ?2",
+                    params![team, pokemon_id],
+                )?;
+            }
+            Problem::MissingNamespace { namespace, .. } => {
+                tx.execute(
+                    "INSERT OR IGNORE INTO namespaces (name, owner) VALUES (?1, ?2)",
+                    params![namespace, DEFAULT_TRAINER],
+                )?;
+            }
+            Problem::UnknownType {
+                table,
+                row_id,
+                value,
+            } => {
+                let bump = if *table == "pokemon" {
+                    ", version = version + 1"
+                } else {
+                    ""
+                };
+                tx.execute(
+                    &format!(
+                        "UPDATE {} SET pokemon_type = ?1{} WHERE id = ?2",
+                        table, bump
+                    ),
+                    params![repaired_type(value).to_string(), row_id],
+                )?;
+            }
+            Problem::UnknownSecondaryType { pokemon_id, value } => {
+                let secondary = PokemonType::from_str(value).ok().map(|t| t.to_string());
+                tx.execute(
+                    "UPDATE pokemon SET secondary_type = ?1, version = version + 1 WHERE id = ?2",
+                    params![secondary, pokemon_id],
+                )?;
+            }
+            Problem::UnknownStatusCondition { pokemon_id, value } => {
+                let status = StatusCondition::from_str(value).ok().map(|s| s.to_string());
+                tx.execute(
+                    "UPDATE pokemon SET status_condition = ?1, version = version + 1 WHERE id = ?2",
+                    params![status, pokemon_id],
+                )?;
+            }
+            Problem::HpAboveMax { pokemon_id, .. } => {
+                tx.execute(
+                    "UPDATE pokemon SET hp = max_hp, version = version + 1 WHERE id = ?1",
+                    params![pokemon_id],
+                )?;
+            }
+            Problem::TooManyMoves { pokemon_id, .. } => {
+                tx.execute(
+                    "DELETE FROM moves WHERE pokemon_id = ?1 AND id NOT IN (
+                         SELECT id FROM moves WHERE pokemon_id = ?1 ORDER BY id LIMIT ?2
+                     )",
+                    params![pokemon_id, MAX_MOVES as i64],
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(problems)
+}
@@ -4,28 +4,20 @@ use std::collections::HashMap;
 pub struct Evolution {
     #[allow(dead_code)]
     pub from: String,
-    #[allow(dead_code)]
     pub to: String,
-    #[allow(dead_code)]
     pub level: u32,
-    #[allow(dead_code)]
     pub stat_multipliers: StatMultipliers,
 }
 
 #[derive(Debug, Clone)]
 pub struct StatMultipliers {
-    #[allow(dead_code)]
     pub hp: f32,
-    #[allow(dead_code)]
     pub attack: f32,
-    #[allow(dead_code)]
     pub defense: f32,
-    #[allow(dead_code)]
     pub speed: f32,
 }
 
 pub struct EvolutionManager {
-    #[allow(dead_code)]
     evolutions: HashMap<String, Evolution>,
 }
 
@@ -51,7 +43,6 @@ impl EvolutionManager {
         EvolutionManager { evolutions }
     }
 
-    #[allow(dead_code)]
     pub fn get_evolution(&self, pokemon_name: &str) -> Option<&Evolution> {
         self.evolutions.get(pokemon_name)
     }
@@ -0,0 +1,196 @@
+use crate::ai::{BattleAi, BattleView};
+use crate::battle::Action;
+use crate::container::Container;
+use crate::moves::{MoveCategory, TypeEffectiveness};
+use colored::Colorize;
+use rand::RngCore;
+use std::io::{BufRead, Write};
+
+/// The numbered move menu of `battle --interactive`. It reads the user's choice
+/// from `input` and writes the menu to `output`.
+pub struct MoveMenu<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> MoveMenu<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    /// Asks which move `pokemon` should use against `opponent` until it gets a
+    /// usable one. Returns `None` if the user forfeits or the input runs out.
+    pub fn choose(
+        &mut self,
+        pokemon: &Container,
+        opponent: &Container,
+        chart: &TypeEffectiveness,
+    ) -> Option<Action> {
+        if !pokemon.moves.iter().any(|m| m.is_usable()) {
+            return Some(Action::Struggle);
+        }
+        self.show(pokemon, opponent, chart).ok()?;
+        loop {
+            write!(self.output, "> ").ok()?;
+            self.output.flush().ok()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let answer = line.trim();
+            if answer.eq_ignore_ascii_case("f") {
+                return None;
+            }
+            let index = answer.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+            match index.and_then(|i| pokemon.moves.get(i).map(|m| (i, m))) {
+                Some((i, battle_move)) if battle_move.is_usable() => {
+                    return Some(Action::Move(i));
+                }
+                Some((_, battle_move)) => writeln!(
+                    self.output,
+                    "{}",
+                    format!("⚠️ {} has no PP left!", battle_move.name).bright_red()
+                )
+                .ok()?,
+                None => writeln!(
+                    self.output,
+                    "{}",
+                    format!(
+                        "⚠️ Pick a move from 1 to {}, or f to forfeit",
+                        pokemon.moves.len()
+                    )
+                    .bright_red()
+                )
+                .ok()?,
+            }
+        }
+    }
+
+    fn show(
+        &mut self,
+        pokemon: &Container,
+        opponent: &Container,
+        chart: &TypeEffectiveness,
+    ) -> std::io::Result<()> {
+        writeln!(
+            self.output,
+            "{} HP {}/{}  vs  {} HP {}/{}",
+            pokemon.name.bright_yellow(),
+            pokemon.hp,
+            pokemon.max_hp,
+            opponent.name.bright_yellow(),
+            opponent.hp,
+            opponent.max_hp
+        )?;
+        writeln!(self.output, "What will {} do?", pokemon.name)?;
+        for (i, battle_move) in pokemon.moves.iter().enumerate() {
+            let hint = if battle_move.category == MoveCategory::Status {
+                String::new()
+            } else {
+                effectiveness_hint(chart.get_dual_multiplier(
+                    battle_move.pokemon_type,
+                    opponent.pokemon_type,
+                    opponent.secondary_type,
+                ))
+            };
+            let line = format!(
+                "  {}. {:<14} {:<9} PP {:>2}/{:<2} {}",
+                i + 1,
+                battle_move.name,
+                battle_move.pokemon_type.to_string(),
+                battle_move.pp,
+                battle_move.max_pp,
+                hint
+            );
+            writeln!(self.output, "{}", line.trim_end())?;
+        }
+        writeln!(self.output, "  f. Forfeit")
+    }
+}
+
+/// The user plays one side of the battle.
+impl<R: BufRead, W: Write> BattleAi for MoveMenu<R, W> {
+    fn choose_action(
+        &mut self,
+        pokemon: &Container,
+        opponent: &Container,
+        view: &BattleView,
+        _rng: &mut dyn RngCore,
+    ) -> Option<Action> {
+        self.choose(pokemon, opponent, view.chart)
+    }
+}
+
+fn effectiveness_hint(multiplier: f32) -> String {
+    if multiplier == 0.0 {
+        "no effect".dimmed().to_string()
+    } else if multiplier > 1.0 {
+        "super effective".bright_green().to_string()
+    } else if multiplier < 1.0 {
+        "not very effective".bright_blue().to_string()
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::{Move, PokemonType};
+    use crate::replay::strip_colors;
+
+    fn pikachu() -> Container {
+        let mut pikachu =
+            Container::new("Pikachu", "kanto", 5, 35, 55, 40, 90, PokemonType::Electric);
+        for (name, pokemon_type, pp) in [
+            ("Thunder Shock", PokemonType::Electric, 30),
+            ("Quick Attack", PokemonType::Normal, 0),
+        ] {
+            let mut battle_move = Move::new(
+                name,
+                40,
+                100,
+                30,
+                pokemon_type,
+                MoveCategory::Physical,
+                "",
+                None,
+            );
+            battle_move.pp = pp;
+            pikachu.learn_move(battle_move);
+        }
+        pikachu
+    }
+
+    fn choose(input: &str, pokemon: &Container) -> (Option<Action>, String) {
+        let opponent = Container::new("Gyarados", "kanto", 5, 95, 125, 79, 81, PokemonType::Water);
+        let mut output = Vec::new();
+        let action = MoveMenu::new(input.as_bytes(), &mut output).choose(
+            pokemon,
+            &opponent,
+            &TypeEffectiveness::new(),
+        );
+        (action, strip_colors(&String::from_utf8(output).unwrap()))
+    }
+
+    #[test]
+    fn test_menu_rejects_bad_choices_until_a_usable_move() {
+        let (action, output) = choose("7\n2\nzap\n1\n", &pikachu());
+        assert!(matches!(action, Some(Action::Move(0))));
+        assert!(output.contains("1. Thunder Shock  Electric  PP 30/30 super effective"));
+        assert!(output.contains("⚠️ Quick Attack has no PP left!"));
+        assert_eq!(output.matches("Pick a move from 1 to 2").count(), 2);
+    }
+
+    #[test]
+    fn test_menu_forfeits_and_struggles() {
+        assert!(choose("f\n", &pikachu()).0.is_none());
+        assert!(choose("", &pikachu()).0.is_none());
+
+        let mut tired = pikachu();
+        tired.moves[0].pp = 0;
+        let (action, output) = choose("", &tired);
+        assert!(matches!(action, Some(Action::Struggle)));
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::database::write_transaction;
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::Error;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result, Row};
+use std::collections::HashMap;
+
+/// Tables whose rows are journaled, parents before children: restored rows are
+/// written in this order and rows to drop are deleted in reverse.
+const JOURNALED_TABLES: &[&str] = &[
+    "namespaces",
+    "pokemon",
+    "moves",
+    "teams",
+    "team_members",
+    "battle_records",
+    "battle_moves",
+    "trainer_stats",
+    "challenges",
+    "inventory",
+];
+
+/// Operations kept per trainer; older ones can no longer be undone.
+const JOURNAL_KEEP: i64 = 100;
+
+/// One journaled command and the number of rows it changed.
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub id: i64,
+    pub command: String,
+    pub created_at: DateTime<Utc>,
+    pub undone: bool,
+    pub rows: u32,
+}
+
+#[derive(Debug)]
+pub enum UndoOutcome {
+    /// The entries that were rolled back, newest first.
+    Undone(Vec<JournalEntry>),
+    /// `later` changed rows that `entry` also changed, so nothing was undone.
+    Conflict {
+        entry: JournalEntry,
+        later: JournalEntry,
+    },
+    NothingToUndo,
+}
+
+struct Column {
+    name: String,
+    /// The table's INTEGER PRIMARY KEY, which is an alias for the rowid.
+    rowid_alias: bool,
+}
+
+const ENTRY_COLUMNS: &str = "j.id, j.command, j.created_at, j.undone,
+     (SELECT COUNT(*) FROM journal_rows r WHERE r.journal_id = j.id)";
+
+fn row_to_entry(row: &Row) -> Result<JournalEntry> {
+    Ok(JournalEntry {
+        id: row.get(0)?,
+        command: row.get(1)?,
+        created_at: DateTime::from_timestamp(row.get(2)?, 0).unwrap_or_default(),
+        undone: row.get(3)?,
+        rows: row.get(4)?,
+    })
+}
+
+fn columns(conn: &Connection, table: &str) -> Result<Vec<Column>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let info = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    let key_columns = info.iter().filter(|(_, _, pk)| *pk > 0).count();
+    Ok(info
+        .into_iter()
+        .map(|(name, column_type, pk)| Column {
+            rowid_alias: pk == 1 && key_columns == 1 && column_type.eq_ignore_ascii_case("INTEGER"),
+            name,
+        })
+        .collect())
+}
+
+/// Creates this connection's temporary triggers. While an operation is open (see
+/// `begin`), they copy the image a row had before the operation first touched it
+/// into `journal_rows`, or NULL for rows the operation inserted. The triggers are
+/// generated from the current columns, so call this again after the schema changes.
+pub fn install_triggers(conn: &Connection) -> Result<()> {
+    conn.execute_batch("CREATE TEMP TABLE IF NOT EXISTS journal_current (id INTEGER NOT NULL)")?;
+    for table in JOURNALED_TABLES {
+        let image = columns(conn, table)?
+            .iter()
+            .map(|column| format!("'{0}', OLD.{0}", column.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let record = |row: &str, before: &str| {
+            format!(
+                "INSERT INTO journal_rows (journal_id, table_name, row_id, before)
+                 SELECT c.id, '{table}', {row}.rowid, {before} FROM journal_current c
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM journal_rows r
+                     WHERE r.journal_id = c.id AND r.table_name = '{table}' AND r.row_id = {row}.rowid
+                 );"
+            )
+        };
+        conn.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS temp.journal_{table}_insert;
+             DROP TRIGGER IF EXISTS temp.journal_{table}_update;
+             DROP TRIGGER IF EXISTS temp.journal_{table}_delete;
+             CREATE TEMP TRIGGER journal_{table}_insert AFTER INSERT ON main.{table}
+             BEGIN {insert} END;
+             CREATE TEMP TRIGGER journal_{table}_update BEFORE UPDATE ON main.{table}
+             BEGIN {update} END;
+             CREATE TEMP TRIGGER journal_{table}_delete BEFORE DELETE ON main.{table}
+             BEGIN {delete} END;",
+            insert = record("NEW", "NULL"),
+            update = record("OLD", &format!("json_object({})", image)),
+            delete = record("OLD", &format!("json_object({})", image)),
+        ))?;
+    }
+    Ok(())
+}
+
+/// Opens a journal entry for `command`; changes made on this connection are
+/// recorded against it until `end`.
+pub fn begin(conn: &Connection, trainer: &str, command: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO journal (trainer, command, created_at) VALUES (?1, ?2, ?3)",
+        params![trainer, command, Utc::now().timestamp()],
+    )?;
+    let id = conn.last_insert_rowid();
+    conn.execute("DELETE FROM journal_current", [])?;
+    conn.execute("INSERT INTO journal_current (id) VALUES (?1)", params![id])?;
+    Ok(())
+}
+
+/// Closes the open entry, dropping it if the command changed nothing, and prunes
+/// the trainer's journal to the newest `JOURNAL_KEEP` entries.
+pub fn end(conn: &mut Connection, trainer: &str) -> Result<()> {
+    let tx = write_transaction(conn)?;
+    let current: Option<i64> = tx
+        .query_row("SELECT id FROM journal_current", [], |row| row.get(0))
+        .optional()?;
+    tx.execute("DELETE FROM journal_current", [])?;
+    if let Some(id) = current {
+        tx.execute(
+            "DELETE FROM journal
+             WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM journal_rows WHERE journal_id = ?1)",
+            params![id],
+        )?;
+    }
+    let stale = "SELECT id FROM journal WHERE trainer = ?1 ORDER BY id DESC LIMIT -1 OFFSET ?2";
+    tx.execute(
+        &format!("DELETE FROM journal_rows WHERE journal_id IN ({})", stale),
+        params![trainer, JOURNAL_KEEP],
+    )?;
+    tx.execute(
+        &format!("DELETE FROM journal WHERE id IN ({})", stale),
+        params![trainer, JOURNAL_KEEP],
+    )?;
+    tx.commit()
+}
+
+/// The trainer's newest `limit` entries, newest first, including undone ones.
+pub fn history(conn: &Connection, trainer: &str, limit: usize) -> Result<Vec<JournalEntry>> {
+    let sql = format!(
+        "SELECT {} FROM journal j WHERE j.trainer = ?1 ORDER BY j.id DESC LIMIT ?2",
+        ENTRY_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let entries = stmt.query_map(params![trainer, limit as i64], row_to_entry)?;
+    entries.collect()
+}
+
+/// Rolls back the trainer's last `count` operations that haven't been undone yet,
+/// newest first, in one transaction. Refuses, changing nothing, if any later
+/// operation that hasn't been undone changed one of the same rows.
+pub fn undo(conn: &mut Connection, trainer: &str, count: usize) -> Result<UndoOutcome> {
+    let tx = write_transaction(conn)?;
+    // Rows are restored table by table, so references may be briefly dangling.
+    tx.pragma_update(None, "defer_foreign_keys", true)?;
+
+    let sql = format!(
+        "SELECT {} FROM journal j WHERE j.trainer = ?1 AND j.undone = 0
+         ORDER BY j.id DESC LIMIT ?2",
+        ENTRY_COLUMNS
+    );
+    let entries = tx
+        .prepare(&sql)?
+        .query_map(params![trainer, count as i64], row_to_entry)?
+        .collect::<Result<Vec<_>>>()?;
+    if entries.is_empty() {
+        return Ok(UndoOutcome::NothingToUndo);
+    }
+
+    let later_sql = format!(
+        "SELECT {} FROM journal j WHERE j.id > ?1 AND j.undone = 0 AND EXISTS (
+             SELECT 1 FROM journal_rows mine
+             JOIN journal_rows theirs
+               ON theirs.table_name = mine.table_name AND theirs.row_id = mine.row_id
+             WHERE mine.journal_id = ?1 AND theirs.journal_id = j.id
+         )
+         ORDER BY j.id LIMIT 1",
+        ENTRY_COLUMNS
+    );
+    let mut table_columns = HashMap::new();
+    for table in JOURNALED_TABLES {
+        table_columns.insert(*table, columns(&tx, table)?);
+    }
+
+    for entry in &entries {
+        if let Some(later) = tx
+            .query_row(&later_sql, params![entry.id], row_to_entry)
+            .optional()?
+        {
+            return Ok(UndoOutcome::Conflict {
+                entry: entry.clone(),
+                later,
+            });
+        }
+
+        let rows = tx
+            .prepare("SELECT table_name, row_id, before FROM journal_rows WHERE journal_id = ?1")?
+            .query_map(params![entry.id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        // Rows the operation inserted go away, children first...
+        for table in JOURNALED_TABLES.iter().rev() {
+            for (_, row_id, _) in rows.iter().filter(|(t, _, b)| t == table && b.is_none()) {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE rowid = ?1", table),
+                    params![row_id],
+                )?;
+            }
+        }
+        // ...and rows it updated or deleted get their old image back, parents first.
+        for table in JOURNALED_TABLES {
+            for (_, row_id, before) in rows.iter().filter(|(t, _, _)| t == table) {
+                if let Some(before) = before {
+                    restore_row(&tx, table, &table_columns[table], *row_id, before)?;
+                }
+            }
+        }
+
+        tx.execute(
+            "UPDATE journal SET undone = 1 WHERE id = ?1",
+            params![entry.id],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(UndoOutcome::Undone(entries))
+}
+
+/// Writes `image` back to the row, re-inserting it if it was deleted. Columns the
+/// image doesn't have (added by a later migration) keep their current or default
+/// value, and a `version` column keeps counting up so optimistic locking still
+/// notices the change.
+fn restore_row(
+    conn: &Connection,
+    table: &str,
+    columns: &[Column],
+    row_id: i64,
+    image: &str,
+) -> Result<()> {
+    let image: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(image).map_err(|e| Error::ToSqlConversionFailure(Box::new(e)))?;
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS (SELECT 1 FROM {} WHERE rowid = ?1)", table),
+        params![row_id],
+        |row| row.get(0),
+    )?;
+
+    let mut names = Vec::new();
+    let mut values = Vec::new();
+    for column in columns {
+        if column.rowid_alias || (exists && column.name == "version") {
+            continue;
+        }
+        if let Some(value) = image.get(&column.name) {
+            names.push(column.name.as_str());
+            values.push(json_to_value(value));
+        }
+    }
+    values.push(Value::Integer(row_id));
+
+    let sql = if exists {
+        let mut assignments: Vec<String> =
+            names.iter().map(|name| format!("{} = ?", name)).collect();
+        if columns.iter().any(|column| column.name == "version") {
+            assignments.push("version = version + 1".to_string());
+        }
+        format!(
+            "UPDATE {} SET {} WHERE rowid = ?",
+            table,
+            assignments.join(", ")
+        )
+    } else {
+        names.push("rowid");
+        format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            names.join(", "),
+            vec!["?"; names.len()].join(", ")
+        )
+    };
+    conn.execute(&sql, params_from_iter(values))?;
+    Ok(())
+}
+
+fn json_to_value(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::Text(s.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
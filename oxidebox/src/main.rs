@@ -1,3 +1,5 @@
+mod ai;
+mod bundle;
 mod cli;
 mod container;
 mod evolution;
@@ -5,23 +7,88 @@ mod stats;
 mod moves;
 mod battle;
 mod database;
+mod storage;
 mod team;
 mod command;
 mod challenge;
+mod doctor;
+mod journal;
+mod replay;
+mod interactive;
 
-use crate::cli::{Cli, Commands};
+use crate::cli::{Cli, Commands, TeamCommands, TrainerCommands};
 use crate::container::ContainerManager;
 use crate::evolution::EvolutionManager;
-use crate::challenge::ChallengeManager;
+use crate::bundle::NamespaceBundle;
+use crate::challenge::{ChallengeManager, ClaimOutcome};
+use crate::database::Database;
+use crate::journal::UndoOutcome;
+use crate::storage::PokemonQuery;
+use crate::team::TeamManager;
 use clap::Parser;
 use colored::*;
 
 fn main() {
     let cli = Cli::parse();
+
+    if cli.command.is_destructive() {
+        match Database::new()
+            .map_err(|e| e.to_string())
+            .and_then(|db| db.auto_backup())
+        {
+            Ok(Some(path)) => println!(
+                "{}",
+                format!("🗄️ Auto-backup written to {}", path.display()).bright_blue()
+            ),
+            Ok(None) => {}
+            Err(e) => {
+                println!(
+                    "{}",
+                    format!("⚠️ Auto-backup failed, aborting: {}", e).bright_red()
+                );
+                return;
+            }
+        }
+    }
+
     let mut container_manager = ContainerManager::new();
+    if let Some(trainer) = &cli.trainer {
+        match container_manager.use_trainer(trainer) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!(
+                    "{}",
+                    format!(
+                        "⚠️ Trainer {} not found! Create it with `trainer create {}`",
+                        trainer, trainer
+                    )
+                    .bright_red()
+                );
+                return;
+            }
+            Err(e) => {
+                eprintln!("Error loading trainer: {}", e);
+                return;
+            }
+        }
+    }
     let evolution_manager = EvolutionManager::new();
-    let mut challenge_manager = ChallengeManager::new();
-    challenge_manager.generate_daily_challenges();
+    let mut challenge_manager = ChallengeManager::load(container_manager.storage_mut())
+        .unwrap_or_else(|e| {
+            eprintln!("Error loading challenges: {}", e);
+            ChallengeManager::new()
+        });
+
+    let journaled = cli.command.is_journaled();
+    if journaled {
+        let command = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+        if let Err(e) = container_manager.storage_mut().begin_operation(&command) {
+            eprintln!("Error starting journal entry: {}", e);
+            return;
+        }
+    }
+    // Undo rolls back challenge progress too, so the copy loaded above is stale.
+    let save_challenges = !matches!(cli.command, Commands::Undo { .. });
 
     match cli.command {
         Commands::CreateNamespace { name } => {
@@ -59,8 +126,11 @@ fn main() {
             defense,
             speed,
             pokemon_type,
+            secondary_type,
+            sp_attack,
+            sp_defense,
         } => {
-            if container_manager.summon(
+            if let Some(id) = container_manager.summon(
                 &namespace,
                 &name,
                 level,
@@ -69,6 +139,9 @@ fn main() {
                 defense,
                 speed,
                 pokemon_type,
+                secondary_type,
+                sp_attack,
+                sp_defense,
             ) {
                 challenge_manager.update_challenge_progress(crate::challenge::ChallengeType::CatchPokemon, 1);
                 println!(
@@ -76,16 +149,24 @@ fn main() {
                     format!(
                         "🎉 Successfully summoned {}!\n\
                         ┌─────────────────────────────┐\n\
+                        │ ID:     {:<18} │\n\
                         │ Name:   {:<18} │\n\
                         │ Level:  {:<18} │\n\
                         │ HP:     {:<18} │\n\
                         │ Type:   {:<18} │\n\
                         └─────────────────────────────┘",
                         name.bright_yellow().bold(),
+                        id,
                         name,
                         level,
                         hp,
-                        pokemon_type.to_string().bright_magenta()
+                        secondary_type
+                            .filter(|t| *t != pokemon_type)
+                            .map_or(pokemon_type.to_string(), |t| format!(
+                                "{}/{}",
+                                pokemon_type, t
+                            ))
+                            .bright_magenta()
                     )
                     .bright_green()
                 );
@@ -117,8 +198,28 @@ fn main() {
                 println!("{}", format!("⚠️ Container {} not found!", id).bright_red());
             }
         }
-        Commands::List { namespace: _ } => {
-            if let Err(e) = container::ContainerManager::list_all_from_db() {
+        Commands::Release { id } => {
+            if container_manager.release(&id) {
+                println!("{}", format!("👋 Released container: {}", id).bright_green());
+            } else {
+                println!("{}", format!("⚠️ Container {} not found!", id).bright_red());
+            }
+        }
+        Commands::List {
+            namespace,
+            pokemon_type,
+            min_level,
+            limit,
+        } => {
+            let query = PokemonQuery {
+                owner: Some(container_manager.trainer().to_string()),
+                namespace,
+                pokemon_type,
+                min_level,
+                limit,
+                ..PokemonQuery::default()
+            };
+            if let Err(e) = container_manager.list_all_from_db(query) {
                 eprintln!("Error listing containers: {}", e);
             }
         }
@@ -129,10 +230,29 @@ fn main() {
                 println!("{}", format!("⚠️ Container {} not found!", id).bright_red());
             }
         }
-        Commands::Battle { id1, id2 } => {
-            if container_manager.battle(&id1, &id2, &evolution_manager) {
-                challenge_manager.update_challenge_progress(crate::challenge::ChallengeType::BattleWin, 1);
-                challenge_manager.update_challenge_progress(crate::challenge::ChallengeType::UseMoves, 2);
+        Commands::Battle {
+            id1,
+            id2,
+            weather,
+            seed,
+            no_evolve,
+            interactive,
+            ai1,
+            ai2,
+        } => {
+            let evolution_manager = (!no_evolve).then_some(&evolution_manager);
+            if let Some(record) = container_manager.battle(&id1, &id2, weather, seed, evolution_manager, interactive, [ai1, ai2]) {
+                if record.challenger_won() {
+                    challenge_manager.update_challenge_progress(crate::challenge::ChallengeType::BattleWin, 1);
+                }
+                let moves_used = record.challenger_moves.values().sum();
+                if moves_used > 0 {
+                    challenge_manager.update_challenge_progress(crate::challenge::ChallengeType::UseMoves, moves_used);
+                }
+                let levels = record.levels_gained();
+                if levels > 0 {
+                    challenge_manager.update_challenge_progress(crate::challenge::ChallengeType::LevelUp, levels);
+                }
                 println!("{}", "⚔️ Battle completed!".bright_green());
             } else {
                 println!("{}", "⚠️ Battle failed!".bright_red());
@@ -155,25 +275,349 @@ fn main() {
         Commands::Stats => {
             container_manager.display_stats();
         }
+        Commands::Replay {
+            battle_id,
+            speed,
+            format,
+        } => match container_manager.storage().load_battle(&battle_id) {
+            Ok(Some(record)) => replay::replay(&record, speed, format),
+            Ok(None) => println!(
+                "{}",
+                format!("⚠️ Battle {} not found!", battle_id).bright_red()
+            ),
+            Err(e) => println!(
+                "{}",
+                format!("⚠️ Failed to load battle: {}", e).bright_red()
+            ),
+        },
         Commands::Challenges => {
-            challenge_manager.check_daily_reset();
             challenge_manager.display_challenges();
         }
         Commands::ClaimReward { challenge_id } => {
-            if let Some(challenge) = challenge_manager.active_challenges.get(&challenge_id) {
-                if challenge.completed {
-                    println!("{}", format!("🎁 Claimed rewards for challenge: {}", challenge.description).bright_green());
-                    println!("{}", format!("Received: {} EXP, {}", 
-                        challenge.reward_exp,
-                        challenge.reward_items.join(", ")
-                    ).bright_yellow());
-                    container_manager.trainer_stats.total_exp_gained += challenge.reward_exp;
-                } else {
+            match container_manager.claim_challenge(&challenge_id) {
+                Ok(ClaimOutcome::Claimed(challenge)) => {
+                    println!(
+                        "{}",
+                        format!(
+                            "🎁 Claimed rewards for challenge: {}",
+                            challenge.description
+                        )
+                        .bright_green()
+                    );
+                    println!(
+                        "{}",
+                        format!(
+                            "Received: {} EXP, {}",
+                            challenge.reward_exp,
+                            challenge.reward_items.join(", ")
+                        )
+                        .bright_yellow()
+                    );
+                }
+                Ok(ClaimOutcome::NotCompleted) => {
                     println!("{}", "⚠️ Challenge not completed yet!".bright_red());
                 }
+                Ok(ClaimOutcome::AlreadyClaimed) => {
+                    println!("{}", "⚠️ Reward already claimed!".bright_red());
+                }
+                Ok(ClaimOutcome::NotFound) => {
+                    println!("{}", "⚠️ Challenge not found!".bright_red());
+                }
+                Err(e) => println!(
+                    "{}",
+                    format!("⚠️ Failed to claim reward: {}", e).bright_red()
+                ),
+            }
+        }
+        Commands::Backup { file, auto } => {
+            let db = match Database::new() {
+                Ok(db) => db,
+                Err(e) => {
+                    println!(
+                        "{}",
+                        format!("⚠️ Failed to open database: {}", e).bright_red()
+                    );
+                    return;
+                }
+            };
+            if file.is_none() && auto.is_none() {
+                println!("{}", "⚠️ Provide a backup file or --auto N".bright_red());
+            }
+            if let Some(keep) = auto {
+                match db.set_auto_backup_limit(keep) {
+                    Ok(_) if keep == 0 => {
+                        println!("{}", "🗄️ Automatic backups disabled".bright_green())
+                    }
+                    Ok(_) => println!(
+                        "{}",
+                        format!(
+                            "🗄️ Keeping {} automatic backups before destructive commands",
+                            keep
+                        )
+                        .bright_green()
+                    ),
+                    Err(e) => println!(
+                        "{}",
+                        format!("⚠️ Failed to update backup settings: {}", e).bright_red()
+                    ),
+                }
+            }
+            if let Some(file) = file {
+                match db.backup_database(&file) {
+                    Ok(_) => println!(
+                        "{}",
+                        format!("🗄️ Backed up database to {}", file).bright_green()
+                    ),
+                    Err(e) => println!(
+                        "{}",
+                        format!("⚠️ Failed to back up database: {}", e).bright_red()
+                    ),
+                }
+            }
+        }
+        Commands::Restore { file } => {
+            let result = Database::new()
+                .map_err(|e| e.to_string())
+                .and_then(|mut db| db.restore_database(&file));
+            match result {
+                Ok(rollback) => println!(
+                    "{}",
+                    format!(
+                        "♻️ Restored database from {} (previous copy kept at {})",
+                        file,
+                        rollback.display()
+                    )
+                    .bright_green()
+                ),
+                Err(e) => println!(
+                    "{}",
+                    format!("⚠️ Failed to restore database: {}", e).bright_red()
+                ),
+            }
+        }
+        Commands::Export { namespace, output } => {
+            let result = container_manager
+                .export_namespace(&namespace)
+                .and_then(|bundle| bundle.to_json().map(|json| (bundle, json)));
+            match (result, output) {
+                (Ok((_, json)), None) => println!("{}", json),
+                (Ok((bundle, json)), Some(path)) => match std::fs::write(&path, json) {
+                    Ok(_) => println!(
+                        "{}",
+                        format!(
+                            "📦 Exported {} Pokémon from {} to {}",
+                            bundle.containers.len(),
+                            namespace,
+                            path
+                        )
+                        .bright_green()
+                    ),
+                    Err(e) => println!(
+                        "{}",
+                        format!("⚠️ Failed to write {}: {}", path, e).bright_red()
+                    ),
+                },
+                (Err(e), _) => println!(
+                    "{}",
+                    format!("⚠️ Failed to export namespace: {}", e).bright_red()
+                ),
+            }
+        }
+        Commands::Import {
+            file,
+            into,
+            on_conflict,
+        } => {
+            let result = std::fs::read_to_string(&file)
+                .map_err(|e| format!("failed to read {}: {}", file, e))
+                .and_then(|json| NamespaceBundle::from_json(&json))
+                .and_then(|bundle| {
+                    container_manager
+                        .import_bundle(&bundle, into.as_deref(), on_conflict)
+                        .map(|summary| (bundle, summary))
+                });
+            match result {
+                Ok((bundle, summary)) => println!(
+                    "{}",
+                    format!(
+                        "📥 Imported {} Pokémon into {} ({} skipped, {} renamed, {} overwritten)",
+                        summary.imported,
+                        into.as_deref().unwrap_or(&bundle.namespace),
+                        summary.skipped,
+                        summary.renamed,
+                        summary.overwritten
+                    )
+                    .bright_green()
+                ),
+                Err(e) => println!(
+                    "{}",
+                    format!("⚠️ Failed to import bundle: {}", e).bright_red()
+                ),
+            }
+        }
+        Commands::Trainer { command } => match command {
+            TrainerCommands::Create { name } => {
+                match container_manager.storage_mut().create_trainer(&name) {
+                    Ok(true) => println!(
+                        "{}",
+                        format!("🧢 Created trainer: {}", name).bright_green()
+                    ),
+                    Ok(false) => println!(
+                        "{}",
+                        format!("⚠️ Trainer {} already exists!", name).bright_red()
+                    ),
+                    Err(e) => eprintln!("Error creating trainer: {}", e),
+                }
+            }
+            TrainerCommands::Ls => match container_manager.storage().list_trainers() {
+                Ok(trainers) => {
+                    for trainer in trainers {
+                        if trainer == container_manager.trainer() {
+                            println!("{}", format!("👉 {}", trainer).bright_green());
+                        } else {
+                            println!("   {}", trainer);
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Error listing trainers: {}", e),
+            },
+            TrainerCommands::Switch { name } => match container_manager.switch_trainer(&name) {
+                Ok(true) => println!(
+                    "{}",
+                    format!("🔁 Switched to trainer: {}", name).bright_green()
+                ),
+                Ok(false) => println!(
+                    "{}",
+                    format!("⚠️ Trainer {} not found!", name).bright_red()
+                ),
+                Err(e) => eprintln!("Error switching trainer: {}", e),
+            },
+        },
+        Commands::History { limit } => match container_manager.storage().history(limit) {
+            Ok(entries) if entries.is_empty() => {
+                println!("{}", "📜 Nothing to undo yet".bright_yellow());
+            }
+            Ok(entries) => {
+                for entry in entries {
+                    let line = format!(
+                        "#{} {} {} ({} rows)",
+                        entry.id,
+                        entry.created_at.format("%Y-%m-%d %H:%M:%S"),
+                        entry.command,
+                        entry.rows
+                    );
+                    if entry.undone {
+                        println!("{}", format!("{} [undone]", line).dimmed());
+                    } else {
+                        println!("{}", line);
+                    }
+                }
+            }
+            Err(e) => eprintln!("Error loading history: {}", e),
+        },
+        Commands::Undo { count } => match container_manager.storage_mut().undo(count) {
+            Ok(UndoOutcome::Undone(entries)) => {
+                for entry in entries {
+                    println!(
+                        "{}",
+                        format!("↩️ Undid #{}: {}", entry.id, entry.command).bright_green()
+                    );
+                }
+            }
+            Ok(UndoOutcome::Conflict { entry, later }) => println!(
+                "{}",
+                format!(
+                    "⚠️ Can't undo #{} ({}): #{} ({}) changed the same data since. Nothing was undone.",
+                    entry.id, entry.command, later.id, later.command
+                )
+                .bright_red()
+            ),
+            Ok(UndoOutcome::NothingToUndo) => {
+                println!("{}", "⚠️ Nothing to undo!".bright_red());
+            }
+            Err(e) => println!("{}", format!("⚠️ Failed to undo: {}", e).bright_red()),
+        },
+        Commands::Doctor { fix } => {
+            // Through the session's storage, so that a repair is journaled.
+            let result = if fix {
+                container_manager.storage_mut().repair_consistency()
             } else {
-                println!("{}", "⚠️ Challenge not found!".bright_red());
+                container_manager.storage().check_consistency()
+            };
+            match result {
+                Ok(problems) if problems.is_empty() => {
+                    println!("{}", "🩺 No problems found".bright_green());
+                }
+                Ok(problems) => {
+                    for problem in &problems {
+                        if fix {
+                            println!(
+                                "{}",
+                                format!("🔧 {}: {}", problem, problem.fix_description())
+                                    .bright_yellow()
+                            );
+                        } else {
+                            println!("{}", format!("⚠️ {}", problem).bright_red());
+                        }
+                    }
+                    if fix {
+                        println!(
+                            "{}",
+                            format!("🩺 Repaired {} problems", problems.len()).bright_green()
+                        );
+                    } else {
+                        println!(
+                            "{}",
+                            format!(
+                                "🩺 Found {} problems, run `doctor --fix` to repair them",
+                                problems.len()
+                            )
+                            .bright_yellow()
+                        );
+                    }
+                }
+                Err(e) => println!(
+                    "{}",
+                    format!("⚠️ Failed to check database: {}", e).bright_red()
+                ),
             }
         }
+        Commands::Team { command } => {
+            let mut team_manager = match TeamManager::load(container_manager.storage()) {
+                Ok(team_manager) => team_manager,
+                Err(e) => {
+                    eprintln!("Error loading teams: {}", e);
+                    return;
+                }
+            };
+            let storage = container_manager.storage_mut();
+            match command {
+                TeamCommands::Create { name } => {
+                    team_manager.create_team(storage, &name);
+                }
+                TeamCommands::Add { team, id, lead } => {
+                    team_manager.add_to_team(storage, &team, &id, lead);
+                }
+                TeamCommands::Remove { team, id } => {
+                    team_manager.remove_from_team(storage, &team, &id);
+                }
+                TeamCommands::Ls => team_manager.list_teams(),
+                TeamCommands::Show { team } => team_manager.team_info(&team, storage),
+                TeamCommands::Rm { team } => {
+                    team_manager.delete_team(storage, &team);
+                }
+            }
+        }
+    }
+
+    if save_challenges {
+        if let Err(e) = challenge_manager.save(container_manager.storage_mut()) {
+            eprintln!("Error saving challenge progress: {}", e);
+        }
+    }
+    if journaled {
+        if let Err(e) = container_manager.storage_mut().end_operation() {
+            eprintln!("Error closing journal entry: {}", e);
+        }
     }
 }
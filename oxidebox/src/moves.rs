@@ -78,15 +78,81 @@ pub struct Move {
     pub category: MoveCategory,
     pub description: String,
     pub effect: Option<MoveEffect>,
+    /// Moves with higher priority go first regardless of speed; see `move_priority`.
+    #[serde(default)]
+    pub priority: i8,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub const STRUGGLE: &str = "Struggle";
+
+/// Moves whose priority isn't 0, by name.
+const MOVE_PRIORITIES: &[(&str, i8)] = &[
+    ("Helping Hand", 5),
+    ("Protect", 4),
+    ("Detect", 4),
+    ("Endure", 4),
+    ("Fake Out", 3),
+    ("Extreme Speed", 2),
+    ("Feint", 2),
+    ("Quick Attack", 1),
+    ("Mach Punch", 1),
+    ("Aqua Jet", 1),
+    ("Bullet Punch", 1),
+    ("Ice Shard", 1),
+    ("Shadow Sneak", 1),
+    ("Sucker Punch", 1),
+    ("Vacuum Wave", 1),
+    ("Accelerock", 1),
+    ("Vital Throw", -1),
+    ("Focus Punch", -3),
+    ("Avalanche", -4),
+    ("Revenge", -4),
+    ("Counter", -5),
+    ("Mirror Coat", -5),
+    ("Roar", -6),
+    ("Whirlwind", -6),
+    ("Dragon Tail", -6),
+    ("Circle Throw", -6),
+    ("Trick Room", -7),
+];
+
+/// The priority of the move called `name`: +1 for Quick Attack, -6 for Roar and
+/// so on, and 0 for moves without one. Priority is a property of the move, so it
+/// isn't stored with a Pokémon's moves.
+pub fn move_priority(name: &str) -> i8 {
+    MOVE_PRIORITIES
+        .iter()
+        .find(|(move_name, _)| move_name.eq_ignore_ascii_case(name))
+        .map_or(0, |&(_, priority)| priority)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MoveCategory {
+    /// Uses attack against defense.
     Physical,
+    /// Uses special attack against special defense.
     Special,
+    /// Does no damage, only applies the move's effect.
     Status,
 }
 
+impl fmt::Display for MoveCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for MoveCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Physical, Self::Special, Self::Status]
+            .into_iter()
+            .find(|category| category.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Invalid move category: {}", s))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MoveEffect {
     pub effect_type: EffectType,
@@ -101,7 +167,7 @@ pub enum EffectType {
     WeatherChange(Weather),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Stat {
     Attack,
     Defense,
@@ -112,7 +178,86 @@ pub enum Stat {
     Evasion,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stat::Attack => "Attack",
+            Stat::Defense => "Defense",
+            Stat::SpecialAttack => "Special Attack",
+            Stat::SpecialDefense => "Special Defense",
+            Stat::Speed => "Speed",
+            Stat::Accuracy => "accuracy",
+            Stat::Evasion => "evasiveness",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How far each stat has been raised or lowered during a battle, from
+/// `MIN_STAGE` to `MAX_STAGE`. Stages only last until the battle ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatStages {
+    pub attack: i8,
+    pub defense: i8,
+    pub special_attack: i8,
+    pub special_defense: i8,
+    pub speed: i8,
+    pub accuracy: i8,
+    pub evasion: i8,
+}
+
+impl StatStages {
+    pub const MIN_STAGE: i8 = -6;
+    pub const MAX_STAGE: i8 = 6;
+
+    pub fn get(&self, stat: Stat) -> i8 {
+        match stat {
+            Stat::Attack => self.attack,
+            Stat::Defense => self.defense,
+            Stat::SpecialAttack => self.special_attack,
+            Stat::SpecialDefense => self.special_defense,
+            Stat::Speed => self.speed,
+            Stat::Accuracy => self.accuracy,
+            Stat::Evasion => self.evasion,
+        }
+    }
+
+    /// Raises (or lowers, for negative `stages`) `stat` as far as the limits
+    /// allow and returns how many stages it actually moved.
+    pub fn change(&mut self, stat: Stat, stages: i8) -> i8 {
+        let stage = match stat {
+            Stat::Attack => &mut self.attack,
+            Stat::Defense => &mut self.defense,
+            Stat::SpecialAttack => &mut self.special_attack,
+            Stat::SpecialDefense => &mut self.special_defense,
+            Stat::Speed => &mut self.speed,
+            Stat::Accuracy => &mut self.accuracy,
+            Stat::Evasion => &mut self.evasion,
+        };
+        let before = *stage;
+        *stage = stage
+            .saturating_add(stages)
+            .clamp(Self::MIN_STAGE, Self::MAX_STAGE);
+        *stage - before
+    }
+
+    /// The multiplier for a stage of Attack, Defense, Special Attack, Special
+    /// Defense or Speed: 2/8 at -6, 1 at 0 and 8/2 at +6.
+    pub fn multiplier(stage: i8) -> f32 {
+        let stage = stage.clamp(Self::MIN_STAGE, Self::MAX_STAGE) as f32;
+        (2.0 + stage.max(0.0)) / (2.0 - stage.min(0.0))
+    }
+
+    /// The multiplier for the hit chance at a stage of accuracy minus evasion:
+    /// 3/9 at -6, 1 at 0 and 9/3 at +6.
+    pub fn accuracy_multiplier(stage: i8) -> f32 {
+        let stage = stage.clamp(Self::MIN_STAGE, Self::MAX_STAGE) as f32;
+        (3.0 + stage.max(0.0)) / (3.0 - stage.min(0.0))
+    }
+}
+
+/// A non-volatile status: it stays on the Pokémon after the battle ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StatusCondition {
     Burn,
     Freeze,
@@ -121,7 +266,49 @@ pub enum StatusCondition {
     Sleep,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl StatusCondition {
+    pub fn all() -> [Self; 5] {
+        [
+            Self::Burn,
+            Self::Freeze,
+            Self::Paralysis,
+            Self::Poison,
+            Self::Sleep,
+        ]
+    }
+
+    /// Types that can't get this status: Fire can't be burned, Ice can't be
+    /// frozen, Electric can't be paralyzed and Poison and Steel can't be poisoned.
+    pub fn immune_types(&self) -> &'static [PokemonType] {
+        match self {
+            Self::Burn => &[PokemonType::Fire],
+            Self::Freeze => &[PokemonType::Ice],
+            Self::Paralysis => &[PokemonType::Electric],
+            Self::Poison => &[PokemonType::Poison, PokemonType::Steel],
+            Self::Sleep => &[],
+        }
+    }
+}
+
+impl fmt::Display for StatusCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for StatusCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|status| status.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Invalid status condition: {}", s))
+    }
+}
+
+/// Weather over the whole battlefield, affecting both Pokémon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Weather {
     Sunny,
     Rain,
@@ -129,7 +316,59 @@ pub enum Weather {
     Hail,
 }
 
+impl Weather {
+    pub fn all() -> [Self; 4] {
+        [Self::Sunny, Self::Rain, Self::Sandstorm, Self::Hail]
+    }
+
+    /// How the weather changes the damage of a move of `move_type`: sun boosts
+    /// Fire and weakens Water by half, rain the other way round.
+    pub fn damage_multiplier(&self, move_type: PokemonType) -> f32 {
+        match (self, move_type) {
+            (Self::Sunny, PokemonType::Fire) | (Self::Rain, PokemonType::Water) => 1.5,
+            (Self::Sunny, PokemonType::Water) | (Self::Rain, PokemonType::Fire) => 0.5,
+            _ => 1.0,
+        }
+    }
+
+    /// Whether the weather hurts Pokémon at the end of each turn, and which types
+    /// it spares: sandstorm spares Rock, Ground and Steel, hail spares Ice.
+    pub fn chip_immune_types(&self) -> Option<&'static [PokemonType]> {
+        match self {
+            Self::Sandstorm => Some(&[PokemonType::Rock, PokemonType::Ground, PokemonType::Steel]),
+            Self::Hail => Some(&[PokemonType::Ice]),
+            Self::Sunny | Self::Rain => None,
+        }
+    }
+}
+
+impl fmt::Display for Weather {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for Weather {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("sun") {
+            return Ok(Self::Sunny);
+        }
+        Self::all()
+            .into_iter()
+            .find(|weather| weather.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "Invalid weather: {} (expected sunny, rain, sandstorm or hail)",
+                    s
+                )
+            })
+    }
+}
+
 impl Move {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: &str,
         power: u32,
@@ -150,10 +389,29 @@ impl Move {
             category,
             description: description.to_string(),
             effect,
+            priority: move_priority(name),
         }
     }
 
-    #[allow(dead_code)]
+    /// The move a Pokémon falls back to when none of its moves has PP left. It
+    /// never misses, ignores types and hurts the user too.
+    pub fn struggle() -> Self {
+        Self::new(
+            STRUGGLE,
+            50,
+            100,
+            1,
+            PokemonType::Normal,
+            MoveCategory::Physical,
+            "Used only when no other move has PP left. Also hurts the user.",
+            None,
+        )
+    }
+
+    pub fn is_struggle(&self) -> bool {
+        self.name == STRUGGLE
+    }
+
     pub fn use_move(&mut self) -> Result<(), String> {
         if self.pp == 0 {
             return Err(format!("No PP left for {}!", self.name));
@@ -167,15 +425,155 @@ impl Move {
         self.pp = (self.pp + amount).min(self.max_pp);
     }
 
-    #[allow(dead_code)]
     pub fn is_usable(&self) -> bool {
         self.pp > 0
     }
 }
 
+/// Every matchup that isn't neutral, as (move type, defender type, multiplier).
+const TYPE_CHART: &[(PokemonType, PokemonType, f32)] = &[
+    // Normal type
+    (PokemonType::Normal, PokemonType::Rock, 0.5),
+    (PokemonType::Normal, PokemonType::Ghost, 0.0),
+    (PokemonType::Normal, PokemonType::Steel, 0.5),
+    // Fire type
+    (PokemonType::Fire, PokemonType::Fire, 0.5),
+    (PokemonType::Fire, PokemonType::Water, 0.5),
+    (PokemonType::Fire, PokemonType::Grass, 2.0),
+    (PokemonType::Fire, PokemonType::Ice, 2.0),
+    (PokemonType::Fire, PokemonType::Bug, 2.0),
+    (PokemonType::Fire, PokemonType::Rock, 0.5),
+    (PokemonType::Fire, PokemonType::Dragon, 0.5),
+    (PokemonType::Fire, PokemonType::Steel, 2.0),
+    // Water type
+    (PokemonType::Water, PokemonType::Fire, 2.0),
+    (PokemonType::Water, PokemonType::Water, 0.5),
+    (PokemonType::Water, PokemonType::Grass, 0.5),
+    (PokemonType::Water, PokemonType::Ground, 2.0),
+    (PokemonType::Water, PokemonType::Rock, 2.0),
+    (PokemonType::Water, PokemonType::Dragon, 0.5),
+    // Electric type
+    (PokemonType::Electric, PokemonType::Water, 2.0),
+    (PokemonType::Electric, PokemonType::Electric, 0.5),
+    (PokemonType::Electric, PokemonType::Grass, 0.5),
+    (PokemonType::Electric, PokemonType::Ground, 0.0),
+    (PokemonType::Electric, PokemonType::Flying, 2.0),
+    (PokemonType::Electric, PokemonType::Dragon, 0.5),
+    // Grass type
+    (PokemonType::Grass, PokemonType::Fire, 0.5),
+    (PokemonType::Grass, PokemonType::Water, 2.0),
+    (PokemonType::Grass, PokemonType::Grass, 0.5),
+    (PokemonType::Grass, PokemonType::Poison, 0.5),
+    (PokemonType::Grass, PokemonType::Ground, 2.0),
+    (PokemonType::Grass, PokemonType::Flying, 0.5),
+    (PokemonType::Grass, PokemonType::Bug, 0.5),
+    (PokemonType::Grass, PokemonType::Rock, 2.0),
+    (PokemonType::Grass, PokemonType::Dragon, 0.5),
+    (PokemonType::Grass, PokemonType::Steel, 0.5),
+    // Ice type
+    (PokemonType::Ice, PokemonType::Fire, 0.5),
+    (PokemonType::Ice, PokemonType::Water, 0.5),
+    (PokemonType::Ice, PokemonType::Grass, 2.0),
+    (PokemonType::Ice, PokemonType::Ice, 0.5),
+    (PokemonType::Ice, PokemonType::Ground, 2.0),
+    (PokemonType::Ice, PokemonType::Flying, 2.0),
+    (PokemonType::Ice, PokemonType::Dragon, 2.0),
+    (PokemonType::Ice, PokemonType::Steel, 0.5),
+    // Fighting type
+    (PokemonType::Fighting, PokemonType::Normal, 2.0),
+    (PokemonType::Fighting, PokemonType::Ice, 2.0),
+    (PokemonType::Fighting, PokemonType::Poison, 0.5),
+    (PokemonType::Fighting, PokemonType::Flying, 0.5),
+    (PokemonType::Fighting, PokemonType::Psychic, 0.5),
+    (PokemonType::Fighting, PokemonType::Bug, 0.5),
+    (PokemonType::Fighting, PokemonType::Rock, 2.0),
+    (PokemonType::Fighting, PokemonType::Ghost, 0.0),
+    (PokemonType::Fighting, PokemonType::Dark, 2.0),
+    (PokemonType::Fighting, PokemonType::Steel, 2.0),
+    (PokemonType::Fighting, PokemonType::Fairy, 0.5),
+    // Poison type
+    (PokemonType::Poison, PokemonType::Grass, 2.0),
+    (PokemonType::Poison, PokemonType::Poison, 0.5),
+    (PokemonType::Poison, PokemonType::Ground, 0.5),
+    (PokemonType::Poison, PokemonType::Rock, 0.5),
+    (PokemonType::Poison, PokemonType::Ghost, 0.5),
+    (PokemonType::Poison, PokemonType::Steel, 0.0),
+    (PokemonType::Poison, PokemonType::Fairy, 2.0),
+    // Ground type
+    (PokemonType::Ground, PokemonType::Fire, 2.0),
+    (PokemonType::Ground, PokemonType::Electric, 2.0),
+    (PokemonType::Ground, PokemonType::Grass, 0.5),
+    (PokemonType::Ground, PokemonType::Poison, 2.0),
+    (PokemonType::Ground, PokemonType::Flying, 0.0),
+    (PokemonType::Ground, PokemonType::Bug, 0.5),
+    (PokemonType::Ground, PokemonType::Rock, 2.0),
+    (PokemonType::Ground, PokemonType::Steel, 2.0),
+    // Flying type
+    (PokemonType::Flying, PokemonType::Electric, 0.5),
+    (PokemonType::Flying, PokemonType::Grass, 2.0),
+    (PokemonType::Flying, PokemonType::Fighting, 2.0),
+    (PokemonType::Flying, PokemonType::Bug, 2.0),
+    (PokemonType::Flying, PokemonType::Rock, 0.5),
+    (PokemonType::Flying, PokemonType::Steel, 0.5),
+    // Psychic type
+    (PokemonType::Psychic, PokemonType::Fighting, 2.0),
+    (PokemonType::Psychic, PokemonType::Poison, 2.0),
+    (PokemonType::Psychic, PokemonType::Psychic, 0.5),
+    (PokemonType::Psychic, PokemonType::Dark, 0.0),
+    (PokemonType::Psychic, PokemonType::Steel, 0.5),
+    // Bug type
+    (PokemonType::Bug, PokemonType::Fire, 0.5),
+    (PokemonType::Bug, PokemonType::Grass, 2.0),
+    (PokemonType::Bug, PokemonType::Fighting, 0.5),
+    (PokemonType::Bug, PokemonType::Poison, 0.5),
+    (PokemonType::Bug, PokemonType::Flying, 0.5),
+    (PokemonType::Bug, PokemonType::Psychic, 2.0),
+    (PokemonType::Bug, PokemonType::Ghost, 0.5),
+    (PokemonType::Bug, PokemonType::Dark, 2.0),
+    (PokemonType::Bug, PokemonType::Steel, 0.5),
+    (PokemonType::Bug, PokemonType::Fairy, 0.5),
+    // Rock type
+    (PokemonType::Rock, PokemonType::Fire, 2.0),
+    (PokemonType::Rock, PokemonType::Ice, 2.0),
+    (PokemonType::Rock, PokemonType::Fighting, 0.5),
+    (PokemonType::Rock, PokemonType::Ground, 0.5),
+    (PokemonType::Rock, PokemonType::Flying, 2.0),
+    (PokemonType::Rock, PokemonType::Bug, 2.0),
+    (PokemonType::Rock, PokemonType::Steel, 0.5),
+    // Ghost type
+    (PokemonType::Ghost, PokemonType::Normal, 0.0),
+    (PokemonType::Ghost, PokemonType::Psychic, 2.0),
+    (PokemonType::Ghost, PokemonType::Ghost, 2.0),
+    (PokemonType::Ghost, PokemonType::Dark, 0.5),
+    // Dragon type
+    (PokemonType::Dragon, PokemonType::Dragon, 2.0),
+    (PokemonType::Dragon, PokemonType::Steel, 0.5),
+    (PokemonType::Dragon, PokemonType::Fairy, 0.0),
+    // Dark type
+    (PokemonType::Dark, PokemonType::Fighting, 0.5),
+    (PokemonType::Dark, PokemonType::Psychic, 2.0),
+    (PokemonType::Dark, PokemonType::Ghost, 2.0),
+    (PokemonType::Dark, PokemonType::Dark, 0.5),
+    (PokemonType::Dark, PokemonType::Fairy, 0.5),
+    // Steel type
+    (PokemonType::Steel, PokemonType::Fire, 0.5),
+    (PokemonType::Steel, PokemonType::Water, 0.5),
+    (PokemonType::Steel, PokemonType::Electric, 0.5),
+    (PokemonType::Steel, PokemonType::Ice, 2.0),
+    (PokemonType::Steel, PokemonType::Rock, 2.0),
+    (PokemonType::Steel, PokemonType::Steel, 0.5),
+    (PokemonType::Steel, PokemonType::Fairy, 2.0),
+    // Fairy type
+    (PokemonType::Fairy, PokemonType::Fire, 0.5),
+    (PokemonType::Fairy, PokemonType::Fighting, 2.0),
+    (PokemonType::Fairy, PokemonType::Poison, 0.5),
+    (PokemonType::Fairy, PokemonType::Dragon, 2.0),
+    (PokemonType::Fairy, PokemonType::Dark, 2.0),
+    (PokemonType::Fairy, PokemonType::Steel, 0.5),
+];
+
 #[derive(Debug, Clone)]
 pub struct TypeEffectiveness {
-    #[allow(dead_code)]
     effectiveness: HashMap<(PokemonType, PokemonType), f32>,
 }
 
@@ -187,37 +585,138 @@ impl Default for TypeEffectiveness {
 
 impl TypeEffectiveness {
     pub fn new() -> Self {
-        let mut effectiveness = HashMap::new();
-
-        // Normal type
-        effectiveness.insert((PokemonType::Normal, PokemonType::Rock), 0.5);
-        effectiveness.insert((PokemonType::Normal, PokemonType::Ghost), 0.0);
-        effectiveness.insert((PokemonType::Normal, PokemonType::Steel), 0.5);
-
-        // Fire type
-        effectiveness.insert((PokemonType::Fire, PokemonType::Fire), 0.5);
-        effectiveness.insert((PokemonType::Fire, PokemonType::Water), 0.5);
-        effectiveness.insert((PokemonType::Fire, PokemonType::Grass), 2.0);
-        effectiveness.insert((PokemonType::Fire, PokemonType::Ice), 2.0);
-        effectiveness.insert((PokemonType::Fire, PokemonType::Bug), 2.0);
-        effectiveness.insert((PokemonType::Fire, PokemonType::Steel), 2.0);
-        effectiveness.insert((PokemonType::Fire, PokemonType::Rock), 0.5);
-
-        // Water type
-        effectiveness.insert((PokemonType::Water, PokemonType::Fire), 2.0);
-        effectiveness.insert((PokemonType::Water, PokemonType::Water), 0.5);
-        effectiveness.insert((PokemonType::Water, PokemonType::Grass), 0.5);
-        effectiveness.insert((PokemonType::Water, PokemonType::Ground), 2.0);
-        effectiveness.insert((PokemonType::Water, PokemonType::Rock), 2.0);
-
+        let effectiveness = TYPE_CHART
+            .iter()
+            .map(|&(attacker, defender, multiplier)| ((attacker, defender), multiplier))
+            .collect();
         Self { effectiveness }
     }
 
-    #[allow(dead_code)]
     pub fn get_multiplier(&self, attacker_type: PokemonType, defender_type: PokemonType) -> f32 {
         self.effectiveness
             .get(&(attacker_type, defender_type))
             .copied()
             .unwrap_or(1.0)
     }
+
+    /// The multiplier against a defender with one or two types: the product of the
+    /// multipliers against each, so 4×, 0.25× and immunities come out naturally.
+    pub fn get_dual_multiplier(
+        &self,
+        attacker_type: PokemonType,
+        primary: PokemonType,
+        secondary: Option<PokemonType>,
+    ) -> f32 {
+        let multiplier = self.get_multiplier(attacker_type, primary);
+        match secondary {
+            Some(secondary) if secondary != primary => {
+                multiplier * self.get_multiplier(attacker_type, secondary)
+            }
+            _ => multiplier,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The canonical chart, one row per move type and one column per defender type,
+    // both in `PokemonType::all_types` order: + is 2×, - is 0.5×, 0 is immune.
+    const CANONICAL: [&str; 18] = [
+        "............-0..-.", // Normal
+        ".--.++.....+-.-.+.", // Fire
+        ".+-.-...+...+.-...", // Water
+        "..+--...0+....-...", // Electric
+        ".-+.-..-+-.-+.-.-.", // Grass
+        ".--.+-..++....+.-.", // Ice
+        "+....+.-.---+0.++-", // Fighting
+        "....+..--...--..0+", // Poison
+        ".+.+-..+.0.-+...+.", // Ground
+        "...-+.+....+-...-.", // Flying
+        "......++..-....0-.", // Psychic
+        ".-..+.--.-+..-.+--", // Bug
+        ".+...+-.-+.+....-.", // Rock
+        "0.........+..+.-..", // Ghost
+        "..............+.-0", // Dragon
+        "......-...+..+.-.-", // Dark
+        ".---.+......+...-+", // Steel
+        ".-....+-......++-.", // Fairy
+    ];
+
+    #[test]
+    fn test_chart_matches_canonical_chart() {
+        let chart = TypeEffectiveness::new();
+        let types = PokemonType::all_types();
+        for (attacker, row) in types.iter().zip(CANONICAL) {
+            assert_eq!(row.len(), types.len());
+            for (defender, cell) in types.iter().zip(row.chars()) {
+                let expected = match cell {
+                    '+' => 2.0,
+                    '-' => 0.5,
+                    '0' => 0.0,
+                    _ => 1.0,
+                };
+                assert_eq!(
+                    chart.get_multiplier(*attacker, *defender),
+                    expected,
+                    "{} against {}",
+                    attacker,
+                    defender
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_dual_type_multipliers() {
+        use PokemonType::*;
+        let chart = TypeEffectiveness::new();
+        let cases = [
+            (Rock, Fire, Some(Flying), 4.0),
+            (Electric, Water, Some(Flying), 4.0),
+            (Ice, Dragon, Some(Flying), 4.0),
+            (Fighting, Poison, Some(Bug), 0.25),
+            (Grass, Fire, Some(Flying), 0.25),
+            (Ground, Fire, Some(Flying), 0.0),
+            (Normal, Ghost, Some(Dark), 0.0),
+            (Water, Fire, Some(Ground), 4.0),
+            (Fire, Water, Some(Grass), 1.0),
+            (Water, Fire, None, 2.0),
+            // A repeated type counts once.
+            (Water, Fire, Some(Fire), 2.0),
+        ];
+        for (attacker, primary, secondary, expected) in cases {
+            assert_eq!(
+                chart.get_dual_multiplier(attacker, primary, secondary),
+                expected,
+                "{} against {}/{:?}",
+                attacker,
+                primary,
+                secondary
+            );
+        }
+    }
+
+    #[test]
+    fn test_stat_stages_clamp_and_scale() {
+        let mut stages = StatStages::default();
+        assert_eq!(stages.change(Stat::Attack, 2), 2);
+        assert_eq!(stages.change(Stat::Attack, 5), 4);
+        assert_eq!(stages.change(Stat::Attack, 1), 0);
+        assert_eq!(stages.get(Stat::Attack), 6);
+        assert_eq!(stages.change(Stat::Evasion, -8), -6);
+        assert_eq!(stages.get(Stat::Defense), 0);
+
+        let multipliers: Vec<f32> = [-6, -1, 0, 1, 2, 6]
+            .into_iter()
+            .map(StatStages::multiplier)
+            .collect();
+        assert_eq!(multipliers, [0.25, 2.0 / 3.0, 1.0, 1.5, 2.0, 4.0]);
+        let accuracy: Vec<f32> = [-6, -1, 0, 1, 6]
+            .into_iter()
+            .map(StatStages::accuracy_multiplier)
+            .collect();
+        assert_eq!(accuracy, [1.0 / 3.0, 0.75, 1.0, 4.0 / 3.0, 3.0]);
+    }
 }
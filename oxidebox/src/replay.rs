@@ -0,0 +1,504 @@
+use crate::battle::Side;
+use crate::moves::{Stat, StatusCondition, Weather};
+use crate::stats::BattleRecord;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use std::{fmt, str::FromStr};
+
+/// Pause between turns when replaying at normal speed.
+pub const TURN_DELAY: Duration = Duration::from_millis(800);
+
+/// Something that happened in a battle. The engine emits these as the battle
+/// runs; they are printed straight away and stored with the battle record so
+/// `replay` can show the battle again. Pokémon are referred to by side; the
+/// names are in `Start`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BattleEvent {
+    Start {
+        challenger: String,
+        opponent: String,
+        weather: Option<Weather>,
+    },
+    TurnStart {
+        turn: u32,
+    },
+    /// The Pokémon has no move with PP left and struggles instead.
+    Struggle {
+        side: Side,
+    },
+    MoveUsed {
+        side: Side,
+        move_name: String,
+    },
+    Miss {
+        side: Side,
+    },
+    /// The target's type is immune to the move.
+    NoEffect {
+        side: Side,
+    },
+    Critical,
+    /// Type effectiveness of a hit that wasn't neutral.
+    Effectiveness {
+        multiplier: f32,
+    },
+    /// `side` took `amount` damage from a move and has `hp` left.
+    Damage {
+        side: Side,
+        amount: u32,
+        hp: i32,
+    },
+    Recoil {
+        side: Side,
+        amount: u32,
+        hp: i32,
+    },
+    StatusInflicted {
+        side: Side,
+        status: StatusCondition,
+    },
+    /// Sleep, freeze or paralysis stopped the Pokémon from acting.
+    StatusPrevented {
+        side: Side,
+        status: StatusCondition,
+    },
+    /// The Pokémon woke up or thawed out.
+    StatusCured {
+        side: Side,
+        status: StatusCondition,
+    },
+    /// End-of-turn damage from burn or poison.
+    StatusDamage {
+        side: Side,
+        status: StatusCondition,
+        amount: u32,
+        hp: i32,
+    },
+    /// A move tried to move `stat` by `stages` and moved it by `changed`, which
+    /// is 0 when the stat was already at its limit.
+    StatChanged {
+        side: Side,
+        stat: Stat,
+        stages: i8,
+        changed: i8,
+    },
+    WeatherStarted {
+        weather: Weather,
+    },
+    /// A move that does nothing right now, like Rain Dance in the rain.
+    MoveFailed,
+    WeatherDamage {
+        side: Side,
+        weather: Weather,
+        amount: u32,
+        hp: i32,
+    },
+    WeatherEnded {
+        weather: Weather,
+    },
+    Faint {
+        side: Side,
+    },
+    /// The battle hit `MAX_TURNS` and is decided on remaining HP.
+    OutOfTurns,
+    /// The player gave up an interactive battle.
+    Forfeit {
+        side: Side,
+    },
+    End {
+        winner: Side,
+        turns: u32,
+    },
+    /// EXP the winner earned, reported after the battle.
+    ExpGained {
+        side: Side,
+        amount: u32,
+    },
+    /// One per level gained, with the level reached.
+    LevelUp {
+        side: Side,
+        level: u32,
+    },
+    /// The winner evolved after levelling up.
+    Evolved {
+        side: Side,
+        from: String,
+        to: String,
+    },
+}
+
+/// Turns events into the lines shown during a battle, remembering the
+/// Pokémon's names from `BattleEvent::Start`.
+#[derive(Debug, Default)]
+pub struct Narrator {
+    challenger: String,
+    opponent: String,
+}
+
+impl Narrator {
+    fn name(&self, side: &Side) -> &str {
+        match side {
+            Side::Challenger => &self.challenger,
+            Side::Opponent => &self.opponent,
+        }
+    }
+
+    pub fn narrate(&mut self, event: &BattleEvent) -> String {
+        match event {
+            BattleEvent::Start {
+                challenger,
+                opponent,
+                weather,
+            } => {
+                self.challenger = challenger.clone();
+                self.opponent = opponent.clone();
+                let mut lines = vec![
+                    "╔══════════════════════════════════════╗"
+                        .bright_red()
+                        .to_string(),
+                    format!(
+                        "║ ⚔️  Battle: {} vs {} ║",
+                        challenger.bright_yellow(),
+                        opponent.bright_yellow()
+                    )
+                    .bright_red()
+                    .bold()
+                    .to_string(),
+                    "╠══════════════════════════════════════╣"
+                        .bright_red()
+                        .to_string(),
+                ];
+                if let Some(weather) = weather {
+                    lines.push(weather_message(*weather).bright_blue().to_string());
+                }
+                lines.join("\n")
+            }
+            BattleEvent::TurnStart { turn } => format!("── Turn {} ──", turn).dimmed().to_string(),
+            BattleEvent::Struggle { side } => {
+                format!("😣 {} has no moves left!", self.name(side))
+            }
+            BattleEvent::MoveUsed { side, move_name } => {
+                format!("💫 {} uses {}!", self.name(side), move_name)
+            }
+            BattleEvent::Miss { side } => format!("💨 {}'s attack missed!", self.name(side)),
+            BattleEvent::NoEffect { side } => {
+                format!("🚫 It doesn't affect {}...", self.name(side))
+            }
+            BattleEvent::Critical => "🎯 A critical hit!".bright_yellow().to_string(),
+            BattleEvent::Effectiveness { multiplier } if *multiplier > 1.0 => {
+                "🔥 It's super effective!".bright_green().to_string()
+            }
+            BattleEvent::Effectiveness { .. } => {
+                "🛡️ It's not very effective...".bright_blue().to_string()
+            }
+            BattleEvent::Damage { side, amount, .. } => {
+                format!("💥 {} takes {} damage!", self.name(side), amount)
+            }
+            BattleEvent::Recoil { side, amount, .. } => format!(
+                "🤕 {} is hit with {} recoil damage!",
+                self.name(side),
+                amount
+            ),
+            BattleEvent::StatusInflicted { side, status } => {
+                let message = match status {
+                    StatusCondition::Burn => "was burned!",
+                    StatusCondition::Freeze => "was frozen solid!",
+                    StatusCondition::Paralysis => "is paralyzed! It may be unable to move!",
+                    StatusCondition::Poison => "was poisoned!",
+                    StatusCondition::Sleep => "fell asleep!",
+                };
+                format!("🌀 {} {}", self.name(side), message)
+                    .bright_magenta()
+                    .to_string()
+            }
+            BattleEvent::StatusPrevented { side, status } => match status {
+                StatusCondition::Sleep => format!("💤 {} is fast asleep.", self.name(side)),
+                StatusCondition::Freeze => format!("🧊 {} is frozen solid!", self.name(side)),
+                _ => format!("⚡ {} is paralyzed! It can't move!", self.name(side)),
+            },
+            BattleEvent::StatusCured { side, status } => match status {
+                StatusCondition::Freeze => format!("💧 {} thawed out!", self.name(side)),
+                _ => format!("🌅 {} woke up!", self.name(side)),
+            },
+            BattleEvent::StatusDamage {
+                side,
+                status,
+                amount,
+                ..
+            } => {
+                let cause = match status {
+                    StatusCondition::Burn => "is hurt by its burn",
+                    _ => "is hurt by poison",
+                };
+                format!("🩹 {} {} and loses {} HP!", self.name(side), cause, amount)
+            }
+            BattleEvent::StatChanged {
+                side,
+                stat,
+                stages,
+                changed,
+            } => {
+                let message = match changed {
+                    0 if *stages > 0 => "won't go any higher!",
+                    0 => "won't go any lower!",
+                    1 => "rose!",
+                    2 => "rose sharply!",
+                    3.. => "rose drastically!",
+                    -1 => "fell!",
+                    -2 => "harshly fell!",
+                    _ => "severely fell!",
+                };
+                format!("📊 {}'s {} {}", self.name(side), stat, message)
+                    .bright_cyan()
+                    .to_string()
+            }
+            BattleEvent::WeatherStarted { weather } => {
+                weather_message(*weather).bright_blue().to_string()
+            }
+            BattleEvent::MoveFailed => "💨 But it failed!".to_string(),
+            BattleEvent::WeatherDamage {
+                side,
+                weather,
+                amount,
+                ..
+            } => {
+                let cause = match weather {
+                    Weather::Hail => "is pelted by hail",
+                    _ => "is buffeted by the sandstorm",
+                };
+                format!("🌪️ {} {} and loses {} HP!", self.name(side), cause, amount)
+            }
+            BattleEvent::WeatherEnded { weather } => {
+                let message = match weather {
+                    Weather::Sunny => "☀️ The sunlight faded.",
+                    Weather::Rain => "🌧️ The rain stopped.",
+                    Weather::Sandstorm => "🏜️ The sandstorm subsided.",
+                    Weather::Hail => "🌨️ The hail stopped.",
+                };
+                message.bright_blue().to_string()
+            }
+            BattleEvent::Faint { side } => format!("😵 {} fainted!", self.name(side))
+                .bright_red()
+                .to_string(),
+            BattleEvent::OutOfTurns => "⏱️ The battle ran out of turns!"
+                .bright_yellow()
+                .to_string(),
+            BattleEvent::Forfeit { side } => format!("🏳️ {} forfeited!", self.name(side))
+                .bright_yellow()
+                .to_string(),
+            BattleEvent::End { winner, turns } => [
+                format!(
+                    "║ 🏆 Winner: {} in {} turns! ║",
+                    self.name(winner).bright_green().bold(),
+                    turns
+                )
+                .bright_green()
+                .bold()
+                .to_string(),
+                "╚══════════════════════════════════════╝"
+                    .bright_red()
+                    .to_string(),
+            ]
+            .join("\n"),
+            BattleEvent::ExpGained { side, amount } => {
+                format!("⭐ {} gained {} EXP!", self.name(side), amount)
+                    .bright_yellow()
+                    .to_string()
+            }
+            BattleEvent::Evolved { side, from, to } => {
+                match side {
+                    Side::Challenger => self.challenger = to.clone(),
+                    Side::Opponent => self.opponent = to.clone(),
+                }
+                format!("✨ {} evolved into {}!", from, to)
+                    .bright_magenta()
+                    .bold()
+                    .to_string()
+            }
+            BattleEvent::LevelUp { side, level } => {
+                format!("🆙 {} grew to level {}!", self.name(side), level)
+                    .bright_green()
+                    .bold()
+                    .to_string()
+            }
+        }
+    }
+}
+
+fn weather_message(weather: Weather) -> &'static str {
+    match weather {
+        Weather::Sunny => "☀️ The sunlight is strong!",
+        Weather::Rain => "🌧️ It's raining!",
+        Weather::Sandstorm => "🏜️ A sandstorm is raging!",
+        Weather::Hail => "🌨️ It's hailing!",
+    }
+}
+
+/// `text` without the terminal color codes `colored` adds, which it only adds
+/// when the environment allows.
+#[cfg(test)]
+pub(crate) fn strip_colors(text: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Collects a battle's events, printing each one as it comes in.
+#[derive(Debug, Default)]
+pub struct BattleLog {
+    narrator: Narrator,
+    events: Vec<BattleEvent>,
+}
+
+impl BattleLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn emit(&mut self, event: BattleEvent) {
+        println!("{}", self.narrator.narrate(&event));
+        self.events.push(event);
+    }
+
+    pub fn into_events(self) -> Vec<BattleEvent> {
+        self.events
+    }
+}
+
+/// How fast `replay` plays a battle back, as a multiple of normal speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplaySpeed(pub f32);
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix(['x', 'X']).unwrap_or(s).parse::<f32>() {
+            Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(ReplaySpeed(speed)),
+            _ => Err(format!(
+                "Invalid replay speed: {} (expected a positive multiple like 2x or 0.5x)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayFormat {
+    /// The battle as it was shown live, turn by turn.
+    Text,
+    /// The whole battle record with its events, for other tools.
+    Json,
+}
+
+impl fmt::Display for ReplayFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayFormat::Text => write!(f, "text"),
+            ReplayFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for ReplayFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ReplayFormat::Text),
+            "json" => Ok(ReplayFormat::Json),
+            _ => Err(format!(
+                "Invalid replay format: {} (expected text or json)",
+                s
+            )),
+        }
+    }
+}
+
+/// Shows a stored battle again. Text replays pause `TURN_DELAY` between turns,
+/// divided by `speed`; JSON is written all at once.
+pub fn replay(record: &BattleRecord, speed: ReplaySpeed, format: ReplayFormat) {
+    match format {
+        ReplayFormat::Json => match serde_json::to_string_pretty(record) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error writing battle: {}", e),
+        },
+        ReplayFormat::Text if record.events.is_empty() => println!(
+            "{}",
+            format!(
+                "⚠️ Battle {} was recorded before battles were kept turn by turn: {} beat {} in {} turns",
+                record.id, record.winner, record.loser, record.turns
+            )
+            .bright_yellow()
+        ),
+        ReplayFormat::Text => {
+            let mut narrator = Narrator::default();
+            for event in &record.events {
+                if matches!(event, BattleEvent::TurnStart { turn } if *turn > 1) {
+                    std::thread::sleep(TURN_DELAY.div_f32(speed.0));
+                }
+                println!("{}", narrator.narrate(event));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_round_trip_as_tagged_json() {
+        let events = vec![
+            BattleEvent::Start {
+                challenger: "Pikachu".to_string(),
+                opponent: "Onix".to_string(),
+                weather: Some(Weather::Rain),
+            },
+            BattleEvent::TurnStart { turn: 1 },
+            BattleEvent::StatChanged {
+                side: Side::Opponent,
+                stat: Stat::Attack,
+                stages: -1,
+                changed: -1,
+            },
+            BattleEvent::Faint {
+                side: Side::Opponent,
+            },
+        ];
+        let json = serde_json::to_string(&events).unwrap();
+        assert!(json.contains(r#"{"event":"turn_start","turn":1}"#));
+        assert!(json.contains(r#"{"event":"faint","side":"opponent"}"#));
+        assert_eq!(
+            serde_json::from_str::<Vec<BattleEvent>>(&json).unwrap(),
+            events
+        );
+
+        let mut narrator = Narrator::default();
+        let lines: Vec<String> = events
+            .iter()
+            .map(|e| strip_colors(&narrator.narrate(e)))
+            .collect();
+        assert_eq!(lines[2], "📊 Onix's Attack fell!");
+        assert_eq!(lines[3], "😵 Onix fainted!");
+    }
+
+    #[test]
+    fn test_replay_options_parse() {
+        assert_eq!("2x".parse(), Ok(ReplaySpeed(2.0)));
+        assert_eq!("0.5".parse(), Ok(ReplaySpeed(0.5)));
+        assert!("0x".parse::<ReplaySpeed>().is_err());
+        assert!("fast".parse::<ReplaySpeed>().is_err());
+        assert_eq!("JSON".parse(), Ok(ReplayFormat::Json));
+        assert!("yaml".parse::<ReplayFormat>().is_err());
+    }
+}
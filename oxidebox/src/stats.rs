@@ -2,13 +2,51 @@ use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 use colored::*;
+use crate::moves::PokemonType;
+use crate::replay::BattleEvent;
+use crate::storage::DEFAULT_TRAINER;
 
+/// One finished battle. The challenger is the first Pokémon passed to `battle`
+/// and is the side the trainer's wins and losses are counted for.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BattleRecord {
+    pub id: String,
     pub winner: String,
     pub loser: String,
     pub date: DateTime<Utc>,
     pub turns: u32,
+    pub challenger_id: String,
+    pub opponent_id: String,
+    pub winner_id: String,
+    pub challenger_type: PokemonType,
+    pub opponent_type: PokemonType,
+    pub challenger_damage: u32,
+    pub opponent_damage: u32,
+    pub challenger_moves: HashMap<String, u32>,
+    pub opponent_moves: HashMap<String, u32>,
+    /// The seed the battle's random numbers came from: `battle --seed` with the
+    /// same seed and the same Pokémon plays out the same way. `None` for battles
+    /// recorded before seeds were kept.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Everything that happened, turn by turn, for `replay`. Empty for battles
+    /// recorded before events were kept.
+    #[serde(default)]
+    pub events: Vec<BattleEvent>,
+}
+
+impl BattleRecord {
+    pub fn challenger_won(&self) -> bool {
+        self.winner_id == self.challenger_id
+    }
+
+    /// Levels the winner gained from the battle's EXP.
+    pub fn levels_gained(&self) -> u32 {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, BattleEvent::LevelUp { .. }))
+            .count() as u32
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,13 +88,12 @@ pub struct TimeBasedStats {
 
 #[derive(Debug)]
 pub struct TrainerStats {
+    pub trainer: String,
     #[allow(dead_code)]
     pub pokemon_stats: HashMap<String, PokemonStats>,
-    #[allow(dead_code)]
     pub battle_history: Vec<BattleRecord>,
     pub total_pokemon_caught: u32,
     pub total_pokemon_released: u32,
-    #[allow(dead_code)]
     pub start_date: DateTime<Utc>,
     pub total_battles: u32,
     pub total_wins: u32,
@@ -64,6 +101,8 @@ pub struct TrainerStats {
     pub favorite_pokemon_type: String,
     pub most_used_move: String,
     pub total_exp_gained: u32,
+    /// Reward items collected from challenges, by name.
+    pub inventory: Vec<(String, u32)>,
     #[allow(dead_code)]
     pub time_based_stats: TimeBasedStats,
     pub daily_catches: u32,
@@ -82,6 +121,7 @@ impl TrainerStats {
             daily_catches: 0,
             weekly_wins: 0,
             monthly_exp: 0,
+            trainer: DEFAULT_TRAINER.to_string(),
             pokemon_stats: HashMap::new(),
             battle_history: Vec::new(),
             total_pokemon_caught: 0,
@@ -90,14 +130,15 @@ impl TrainerStats {
             total_battles: 0,
             total_wins: 0,
             total_losses: 0,
-            favorite_pokemon_type: "Normal".to_string(),
-            most_used_move: "Tackle".to_string(),
+            favorite_pokemon_type: "None".to_string(),
+            most_used_move: "None".to_string(),
             total_exp_gained: 0,
+            inventory: Vec::new(),
         }
     }
 
     pub fn display_detailed_stats(&self) {
-        println!("{}", "=== Trainer Statistics ===".bright_cyan());
+        println!("{}", format!("=== Trainer Statistics: {} ===", self.trainer).bright_cyan());
         println!("{}: {}", "Total Pokemon Caught".bright_green(), self.total_pokemon_caught);
         println!("{}: {}", "Total Pokemon Released".bright_green(), self.total_pokemon_released);
         println!("{}: {}", "Total Battles".bright_green(), self.total_battles);
@@ -113,6 +154,27 @@ impl TrainerStats {
         println!("{}: {}", "Favorite Pokemon Type".bright_green(), self.favorite_pokemon_type);
         println!("{}: {}", "Most Used Move".bright_green(), self.most_used_move);
         println!("{}: {}", "Total EXP Gained".bright_green(), self.total_exp_gained);
+        if !self.inventory.is_empty() {
+            println!("{}", "--- Inventory ---".bright_cyan());
+            for (item, quantity) in &self.inventory {
+                println!("🎒 {} x{}", item, quantity);
+            }
+        }
+        if !self.battle_history.is_empty() {
+            println!("{}", "--- Recent Battles ---".bright_cyan());
+            for record in &self.battle_history {
+                println!(
+                    "{} #{} {} {} beat {} in {} turns{}",
+                    if record.challenger_won() { "🏆" } else { "💀" },
+                    record.id,
+                    record.date.format("%Y-%m-%d %H:%M"),
+                    record.winner.bright_yellow(),
+                    record.loser,
+                    record.turns,
+                    record.seed.map(|seed| format!(" (seed {})", seed)).unwrap_or_default()
+                );
+            }
+        }
         println!("{}", "=====================".bright_cyan());
     }
 
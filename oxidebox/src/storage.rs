@@ -0,0 +1,650 @@
+use crate::bundle::ConflictPolicy;
+use crate::challenge::{Challenge, ClaimOutcome};
+use crate::container::Container;
+use crate::database::ImportSummary;
+use crate::doctor::Problem;
+use crate::journal::{JournalEntry, UndoOutcome};
+use crate::moves::{Move, PokemonType};
+use crate::stats::{BattleRecord, TrainerStats};
+use chrono::{DateTime, Utc};
+#[cfg(test)]
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// Trainer that owns everything created before profiles existed, and the one
+/// used until another trainer is switched to.
+pub const DEFAULT_TRAINER: &str = "default";
+
+/// Number of battles shown in `oxidebox stats`.
+pub const RECENT_BATTLES: usize = 5;
+
+/// Persistence used by `ContainerManager`. Container ids are assigned by the
+/// storage when a container is first saved; saving a container whose id the
+/// storage already knows updates it in place.
+///
+/// Updates are optimistically locked: each save bumps the container's `version`,
+/// and saving a copy whose version is stale, or whose container has been deleted
+/// since, fails with `StorageError::Conflict` instead of overwriting someone
+/// else's changes.
+///
+/// Namespaces, battle history, trainer stats, challenges and inventory belong to
+/// the active trainer (see `trainer`). Containers belong to whoever owns their
+/// namespace: `load_pokemon` and `delete_pokemon` don't see another trainer's
+/// containers, so nothing loaded through them can change that trainer's
+/// progression. Teams belong to the trainer who created them.
+pub trait Storage {
+    /// The trainer that namespaces, stats, challenges and inventory are scoped to.
+    fn trainer(&self) -> &str;
+    /// Changes the active trainer for this session only.
+    fn set_trainer(&mut self, name: &str);
+    fn create_trainer(&mut self, name: &str) -> Result<bool>;
+    fn list_trainers(&self) -> Result<Vec<String>>;
+    /// Makes `name` the active trainer, now and for later sessions. Returns false
+    /// if no such trainer exists.
+    fn switch_trainer(&mut self, name: &str) -> Result<bool>;
+    /// Items the active trainer has collected from challenge rewards.
+    fn load_inventory(&self) -> Result<Vec<(String, u32)>>;
+
+    fn create_namespace(&mut self, name: &str) -> Result<bool>;
+    fn delete_namespace(&mut self, name: &str) -> Result<bool>;
+    /// Namespaces owned by the active trainer.
+    fn get_namespaces(&self) -> Result<Vec<String>>;
+    /// The trainer owning `name`, if the namespace exists at all.
+    fn namespace_owner(&self, name: &str) -> Result<Option<String>>;
+
+    /// Saves `container`, updating its id and version, and returns the id.
+    fn save_pokemon(&mut self, container: &mut Container) -> Result<String>;
+    /// Loads one of the active trainer's containers with its moves.
+    fn load_pokemon(&self, id: &str) -> Result<Option<Container>>;
+    /// Deletes one of the active trainer's containers. Returns false if there is
+    /// no such container or it belongs to another trainer.
+    fn delete_pokemon(&mut self, id: &str) -> Result<bool>;
+
+    fn load_moves(&self, id: &str) -> Result<Vec<Move>>;
+
+    /// Returns the Pokémon matching `query`, with their moves, ordered by id.
+    fn query_pokemon(&self, query: &PokemonQuery) -> Result<Vec<Container>>;
+
+    /// Saves both participants and appends `record` to the history as one unit.
+    /// Nothing is saved if either participant has a conflict.
+    fn save_battle(
+        &mut self,
+        challenger: &mut Container,
+        opponent: &mut Container,
+        record: &BattleRecord,
+    ) -> Result<String>;
+    /// Returns the newest `limit` battles, newest first.
+    fn recent_battles(&self, limit: usize) -> Result<Vec<BattleRecord>>;
+    /// Loads one of the current trainer's battles, events included.
+    fn load_battle(&self, id: &str) -> Result<Option<BattleRecord>>;
+    /// Loads the stored counters and derives battle totals, favorite type and
+    /// most-used move from the battle history.
+    fn load_trainer_stats(&self) -> Result<TrainerStats>;
+    /// Stores the counters that aren't derived from history: catches, releases,
+    /// EXP and the start date.
+    fn save_trainer_stats(&mut self, stats: &TrainerStats) -> Result<()>;
+
+    /// Returns the challenges that haven't expired at `now`.
+    fn load_challenges(&self, now: DateTime<Utc>) -> Result<Vec<Challenge>>;
+    /// Inserts new challenges and updates progress on existing ones. The claimed
+    /// flag is only ever changed by `claim_challenge`.
+    fn save_challenges(&mut self, challenges: &[Challenge]) -> Result<()>;
+    /// Marks a completed challenge as claimed and credits its EXP and items to the
+    /// trainer, atomically and at most once.
+    fn claim_challenge(&mut self, id: &str) -> Result<ClaimOutcome>;
+
+    /// Returns the active trainer's teams with their member ids in order, lead
+    /// first.
+    fn load_teams(&self) -> Result<Vec<(String, Vec<String>)>>;
+    /// Creates `name` if needed and replaces its members with `members`, in order.
+    fn save_team(&mut self, name: &str, members: &[String]) -> Result<()>;
+    fn delete_team(&mut self, name: &str) -> Result<bool>;
+
+    /// Writes `containers` into `namespace` atomically, creating the namespace if
+    /// needed. A container conflicts with an existing one when both have the same
+    /// name in the target namespace.
+    fn import_pokemon(
+        &mut self,
+        namespace: &str,
+        containers: &[Container],
+        on_conflict: ConflictPolicy,
+    ) -> Result<ImportSummary>;
+
+    /// Starts recording the changes made by `command` so that they can be undone.
+    /// Storage without a journal records nothing.
+    fn begin_operation(&mut self, _command: &str) -> Result<()> {
+        Ok(())
+    }
+    /// Stops recording the operation started by `begin_operation`.
+    fn end_operation(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// The active trainer's newest `limit` recorded operations, newest first.
+    fn history(&self, _limit: usize) -> Result<Vec<JournalEntry>> {
+        Ok(Vec::new())
+    }
+    /// Rolls back the active trainer's last `count` operations as one unit.
+    fn undo(&mut self, _count: usize) -> Result<UndoOutcome> {
+        Ok(UndoOutcome::NothingToUndo)
+    }
+
+    /// Lists the inconsistencies `oxidebox doctor` looks for, changing nothing.
+    /// Storage that can't get out of step with itself has none.
+    fn check_consistency(&self) -> Result<Vec<Problem>> {
+        Ok(Vec::new())
+    }
+    /// Repairs everything `check_consistency` reports, atomically, and returns
+    /// what was repaired. Goes through the journal like any other change.
+    fn repair_consistency(&mut self) -> Result<Vec<Problem>> {
+        Ok(Vec::new())
+    }
+
+    fn load_namespace_pokemon(&self, namespace: &str) -> Result<Vec<Container>> {
+        self.query_pokemon(&PokemonQuery {
+            namespace: Some(namespace.to_string()),
+            ..PokemonQuery::default()
+        })
+    }
+}
+
+pub type Result<T> = std::result::Result<T, StorageError>;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// A container was saved by someone else after it was loaded. The caller
+    /// should reload it and try again.
+    Conflict,
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Conflict => write!(f, "changed by someone else since it was loaded"),
+            StorageError::Sqlite(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(error: rusqlite::Error) -> Self {
+        StorageError::Sqlite(error)
+    }
+}
+
+/// Filters for `Storage::query_pokemon`. `after_id` and `limit` give keyset
+/// pagination: pass the last id of one page as `after_id` to get the next.
+#[derive(Debug, Clone, Default)]
+pub struct PokemonQuery {
+    /// Only Pokémon in namespaces owned by this trainer.
+    pub owner: Option<String>,
+    pub namespace: Option<String>,
+    /// Pokémon with this as their primary or secondary type.
+    pub pokemon_type: Option<PokemonType>,
+    pub min_level: Option<u32>,
+    pub after_id: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+/// Walks the results of a query one page at a time so that listing a large box
+/// never holds more than `page_size` containers in memory.
+pub struct PokemonPages<'a> {
+    storage: &'a dyn Storage,
+    query: PokemonQuery,
+    page_size: usize,
+    remaining: Option<usize>,
+    done: bool,
+}
+
+impl<'a> PokemonPages<'a> {
+    pub fn new(storage: &'a dyn Storage, query: PokemonQuery, page_size: usize) -> Self {
+        let remaining = query.limit;
+        Self {
+            storage,
+            query,
+            page_size: page_size.max(1),
+            remaining,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for PokemonPages<'_> {
+    type Item = Result<Vec<Container>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let limit = self
+            .remaining
+            .map_or(self.page_size, |r| r.min(self.page_size));
+        if limit == 0 {
+            self.done = true;
+            return None;
+        }
+
+        self.query.limit = Some(limit);
+        match self.storage.query_pokemon(&self.query) {
+            Ok(page) => {
+                self.done = page.len() < limit;
+                self.remaining = self.remaining.map(|r| r - page.len());
+                match page.last().and_then(|last| last.id.parse().ok()) {
+                    Some(last_id) => self.query.after_id = Some(last_id),
+                    None => self.done = true,
+                }
+                if page.is_empty() {
+                    None
+                } else {
+                    Some(Ok(page))
+                }
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Keeps everything in memory; nothing survives the process. Used by tests.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    trainer: String,
+    trainers: BTreeSet<String>,
+    /// Namespace name to owning trainer.
+    namespaces: BTreeMap<String, String>,
+    pokemon: BTreeMap<i64, Container>,
+    next_id: i64,
+    battles: Vec<(String, BattleRecord)>,
+    trainer_stats: BTreeMap<String, TrainerStats>,
+    challenges: BTreeMap<(String, String), Challenge>,
+    inventory: BTreeMap<(String, String), u32>,
+    /// Team members by owning trainer and team name.
+    teams: BTreeMap<(String, String), Vec<String>>,
+}
+
+#[cfg(test)]
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self {
+            trainer: DEFAULT_TRAINER.to_string(),
+            trainers: BTreeSet::from([DEFAULT_TRAINER.to_string()]),
+            ..Self::default()
+        }
+    }
+
+    fn trainer_battles(&self) -> impl DoubleEndedIterator<Item = &BattleRecord> {
+        self.battles
+            .iter()
+            .filter(|(trainer, _)| *trainer == self.trainer)
+            .map(|(_, battle)| battle)
+    }
+
+    fn insert(&mut self, container: &Container) -> i64 {
+        self.next_id += 1;
+        let mut container = container.clone();
+        container.id = self.next_id.to_string();
+        container.version = 0;
+        self.pokemon.insert(self.next_id, container);
+        self.next_id
+    }
+
+    /// Fails with a conflict if `container` is stored with a different version.
+    fn check_version(&self, container: &Container) -> Result<()> {
+        match container
+            .id
+            .parse::<i64>()
+            .ok()
+            .and_then(|id| self.pokemon.get(&id))
+        {
+            Some(stored) if stored.version != container.version => Err(StorageError::Conflict),
+            _ => Ok(()),
+        }
+    }
+
+    fn find_pokemon(&self, namespace: &str, name: &str) -> Option<String> {
+        self.pokemon
+            .values()
+            .find(|container| container.namespace == namespace && container.name == name)
+            .map(|container| container.id.clone())
+    }
+
+    /// Appends a finished battle to the history and returns its id.
+    fn record_battle(&mut self, record: &BattleRecord) -> String {
+        let mut record = record.clone();
+        record.id = (self.battles.len() + 1).to_string();
+        self.battles.push((self.trainer.clone(), record.clone()));
+        record.id
+    }
+}
+
+#[cfg(test)]
+impl Storage for MemoryStorage {
+    fn trainer(&self) -> &str {
+        &self.trainer
+    }
+
+    fn set_trainer(&mut self, name: &str) {
+        self.trainer = name.to_string();
+    }
+
+    fn create_trainer(&mut self, name: &str) -> Result<bool> {
+        Ok(self.trainers.insert(name.to_string()))
+    }
+
+    fn list_trainers(&self) -> Result<Vec<String>> {
+        Ok(self.trainers.iter().cloned().collect())
+    }
+
+    fn switch_trainer(&mut self, name: &str) -> Result<bool> {
+        if !self.trainers.contains(name) {
+            return Ok(false);
+        }
+        self.trainer = name.to_string();
+        Ok(true)
+    }
+
+    fn load_inventory(&self) -> Result<Vec<(String, u32)>> {
+        Ok(self
+            .inventory
+            .iter()
+            .filter(|((trainer, _), quantity)| *trainer == self.trainer && **quantity > 0)
+            .map(|((_, item), quantity)| (item.clone(), *quantity))
+            .collect())
+    }
+
+    fn create_namespace(&mut self, name: &str) -> Result<bool> {
+        if self.namespaces.contains_key(name) {
+            return Ok(false);
+        }
+        self.namespaces
+            .insert(name.to_string(), self.trainer.clone());
+        Ok(true)
+    }
+
+    fn delete_namespace(&mut self, name: &str) -> Result<bool> {
+        if self.namespaces.get(name) != Some(&self.trainer) {
+            return Ok(false);
+        }
+        Ok(self.namespaces.remove(name).is_some())
+    }
+
+    fn get_namespaces(&self) -> Result<Vec<String>> {
+        Ok(self
+            .namespaces
+            .iter()
+            .filter(|(_, owner)| **owner == self.trainer)
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    fn namespace_owner(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.namespaces.get(name).cloned())
+    }
+
+    fn save_pokemon(&mut self, container: &mut Container) -> Result<String> {
+        self.check_version(container)?;
+        match container.id.parse::<i64>() {
+            Ok(id) if self.pokemon.contains_key(&id) => {
+                container.version += 1;
+                self.pokemon.insert(id, container.clone());
+            }
+            // Deleted since it was loaded.
+            Ok(_) => return Err(StorageError::Conflict),
+            Err(_) => {
+                container.id = self.insert(container).to_string();
+                container.version = 0;
+            }
+        }
+        Ok(container.id.clone())
+    }
+
+    fn load_pokemon(&self, id: &str) -> Result<Option<Container>> {
+        Ok(id
+            .parse::<i64>()
+            .ok()
+            .and_then(|id| self.pokemon.get(&id))
+            .filter(|container| self.namespaces.get(&container.namespace) == Some(&self.trainer))
+            .cloned())
+    }
+
+    fn delete_pokemon(&mut self, id: &str) -> Result<bool> {
+        if self.load_pokemon(id)?.is_none() {
+            return Ok(false);
+        }
+        for members in self.teams.values_mut() {
+            members.retain(|member| member != id);
+        }
+        Ok(id
+            .parse::<i64>()
+            .ok()
+            .and_then(|id| self.pokemon.remove(&id))
+            .is_some())
+    }
+
+    fn load_moves(&self, id: &str) -> Result<Vec<Move>> {
+        Ok(self
+            .load_pokemon(id)?
+            .map(|container| container.moves)
+            .unwrap_or_default())
+    }
+
+    fn query_pokemon(&self, query: &PokemonQuery) -> Result<Vec<Container>> {
+        let start = query.after_id.unwrap_or(0).saturating_add(1);
+        Ok(self
+            .pokemon
+            .range(start..)
+            .map(|(_, container)| container)
+            .filter(|c| {
+                query
+                    .owner
+                    .as_ref()
+                    .is_none_or(|owner| self.namespaces.get(&c.namespace) == Some(owner))
+            })
+            .filter(|c| query.namespace.as_ref().is_none_or(|ns| &c.namespace == ns))
+            .filter(|c| query.pokemon_type.is_none_or(|t| c.has_type(t)))
+            .filter(|c| query.min_level.is_none_or(|level| c.level >= level))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect())
+    }
+
+    fn save_battle(
+        &mut self,
+        challenger: &mut Container,
+        opponent: &mut Container,
+        record: &BattleRecord,
+    ) -> Result<String> {
+        self.check_version(challenger)?;
+        self.check_version(opponent)?;
+        self.save_pokemon(challenger)?;
+        self.save_pokemon(opponent)?;
+        Ok(self.record_battle(record))
+    }
+
+    fn recent_battles(&self, limit: usize) -> Result<Vec<BattleRecord>> {
+        Ok(self.trainer_battles().rev().take(limit).cloned().collect())
+    }
+
+    fn load_battle(&self, id: &str) -> Result<Option<BattleRecord>> {
+        Ok(self.trainer_battles().find(|record| record.id == id).cloned())
+    }
+
+    fn load_trainer_stats(&self) -> Result<TrainerStats> {
+        let mut stats = TrainerStats::new();
+        if let Some(stored) = self.trainer_stats.get(&self.trainer) {
+            stats.total_pokemon_caught = stored.total_pokemon_caught;
+            stats.total_pokemon_released = stored.total_pokemon_released;
+            stats.total_exp_gained = stored.total_exp_gained;
+            stats.start_date = stored.start_date;
+        }
+
+        stats.total_battles = self.trainer_battles().count() as u32;
+        stats.total_wins = self.trainer_battles().filter(|b| b.challenger_won()).count() as u32;
+        stats.total_losses = stats.total_battles - stats.total_wins;
+
+        let mut types: HashMap<String, u32> = HashMap::new();
+        let mut moves: HashMap<String, u32> = HashMap::new();
+        for battle in self.trainer_battles() {
+            *types.entry(battle.challenger_type.to_string()).or_default() += 1;
+            for (name, uses) in &battle.challenger_moves {
+                *moves.entry(name.clone()).or_default() += uses;
+            }
+        }
+        // Highest count wins; ties go to the alphabetically first name, as in SQL.
+        let most_frequent = |counts: HashMap<String, u32>| {
+            counts
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                .map(|(name, _)| name)
+        };
+        if let Some(favorite) = most_frequent(types) {
+            stats.favorite_pokemon_type = favorite;
+        }
+        if let Some(most_used) = most_frequent(moves) {
+            stats.most_used_move = most_used;
+        }
+
+        stats.trainer = self.trainer.clone();
+        stats.inventory = self.load_inventory()?;
+        stats.battle_history = self.recent_battles(RECENT_BATTLES)?;
+        Ok(stats)
+    }
+
+    fn save_trainer_stats(&mut self, stats: &TrainerStats) -> Result<()> {
+        let mut stored = TrainerStats::new();
+        stored.total_pokemon_caught = stats.total_pokemon_caught;
+        stored.total_pokemon_released = stats.total_pokemon_released;
+        stored.total_exp_gained = stats.total_exp_gained;
+        stored.start_date = self
+            .trainer_stats
+            .get(&self.trainer)
+            .map_or(stats.start_date, |s| s.start_date);
+        self.trainer_stats.insert(self.trainer.clone(), stored);
+        Ok(())
+    }
+
+    fn load_challenges(&self, now: DateTime<Utc>) -> Result<Vec<Challenge>> {
+        Ok(self
+            .challenges
+            .iter()
+            .filter(|((trainer, _), _)| *trainer == self.trainer)
+            .map(|(_, challenge)| challenge)
+            .filter(|challenge| challenge.expires_at > now)
+            .cloned()
+            .collect())
+    }
+
+    fn save_challenges(&mut self, challenges: &[Challenge]) -> Result<()> {
+        for challenge in challenges {
+            let key = (self.trainer.clone(), challenge.id.clone());
+            let claimed = self
+                .challenges
+                .get(&key)
+                .is_some_and(|stored| stored.claimed);
+            let mut challenge = challenge.clone();
+            challenge.claimed = claimed;
+            self.challenges.insert(key, challenge);
+        }
+        Ok(())
+    }
+
+    fn claim_challenge(&mut self, id: &str) -> Result<ClaimOutcome> {
+        let challenge = match self
+            .challenges
+            .get_mut(&(self.trainer.clone(), id.to_string()))
+        {
+            None => return Ok(ClaimOutcome::NotFound),
+            Some(challenge) if !challenge.completed => return Ok(ClaimOutcome::NotCompleted),
+            Some(challenge) if challenge.claimed => return Ok(ClaimOutcome::AlreadyClaimed),
+            Some(challenge) => {
+                challenge.claimed = true;
+                challenge.clone()
+            }
+        };
+        let mut stats = self.load_trainer_stats()?;
+        stats.total_exp_gained += challenge.reward_exp;
+        self.save_trainer_stats(&stats)?;
+        for item in &challenge.reward_items {
+            *self
+                .inventory
+                .entry((self.trainer.clone(), item.clone()))
+                .or_default() += 1;
+        }
+        Ok(ClaimOutcome::Claimed(challenge))
+    }
+
+    fn load_teams(&self) -> Result<Vec<(String, Vec<String>)>> {
+        Ok(self
+            .teams
+            .iter()
+            .filter(|((owner, _), _)| *owner == self.trainer)
+            .map(|((_, name), members)| (name.clone(), members.clone()))
+            .collect())
+    }
+
+    fn save_team(&mut self, name: &str, members: &[String]) -> Result<()> {
+        self.teams
+            .insert((self.trainer.clone(), name.to_string()), members.to_vec());
+        Ok(())
+    }
+
+    fn delete_team(&mut self, name: &str) -> Result<bool> {
+        Ok(self
+            .teams
+            .remove(&(self.trainer.clone(), name.to_string()))
+            .is_some())
+    }
+
+    fn import_pokemon(
+        &mut self,
+        namespace: &str,
+        containers: &[Container],
+        on_conflict: ConflictPolicy,
+    ) -> Result<ImportSummary> {
+        self.namespaces
+            .entry(namespace.to_string())
+            .or_insert_with(|| self.trainer.clone());
+
+        let mut summary = ImportSummary::default();
+        for container in containers {
+            let mut container = container.clone();
+            container.namespace = namespace.to_string();
+            container
+                .labels
+                .insert("namespace".to_string(), namespace.to_string());
+
+            if let Some(existing) = self.find_pokemon(namespace, &container.name) {
+                match on_conflict {
+                    ConflictPolicy::Skip => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    ConflictPolicy::Rename => {
+                        let mut suffix = 2;
+                        while self
+                            .find_pokemon(namespace, &format!("{}-{}", container.name, suffix))
+                            .is_some()
+                        {
+                            suffix += 1;
+                        }
+                        container.name = format!("{}-{}", container.name, suffix);
+                        summary.renamed += 1;
+                    }
+                    ConflictPolicy::Overwrite => {
+                        self.delete_pokemon(&existing)?;
+                        summary.overwritten += 1;
+                    }
+                }
+            }
+
+            self.insert(&container);
+            summary.imported += 1;
+        }
+        Ok(summary)
+    }
+}
@@ -1,31 +1,42 @@
 use crate::container::Container;
-use std::collections::HashMap;
+use crate::storage::{self, Storage};
+use std::collections::BTreeMap;
 
+pub const MAX_TEAM_SIZE: usize = 6;
+
+/// Teams map to ordered lists of container ids; the first member is the lead.
 pub struct TeamManager {
-    teams: HashMap<String, Vec<String>>,
+    teams: BTreeMap<String, Vec<String>>,
 }
 
 pub struct TeamStats {
-    #[allow(dead_code)]
     pub battles_won: u32,
-    #[allow(dead_code)]
     pub battles_lost: u32,
     pub total_exp: u32,
     pub average_level: f32,
 }
 
-#[allow(dead_code)]
 impl TeamManager {
+    #[allow(dead_code)]
     pub fn new() -> Self {
         TeamManager {
-            teams: HashMap::new(),
+            teams: BTreeMap::new(),
         }
     }
 
-    pub fn create_team(&mut self, team_name: &str) -> bool {
+    pub fn load(storage: &dyn Storage) -> storage::Result<Self> {
+        Ok(TeamManager {
+            teams: storage.load_teams()?.into_iter().collect(),
+        })
+    }
+
+    pub fn create_team(&mut self, storage: &mut dyn Storage, team_name: &str) -> bool {
         if self.teams.contains_key(team_name) {
             println!("⚠️ Team {} already exists!", team_name);
             false
+        } else if let Err(e) = storage.save_team(team_name, &[]) {
+            println!("⚠️ Failed to create team {}: {}", team_name, e);
+            false
         } else {
             self.teams.insert(team_name.to_string(), Vec::new());
             println!("🌟 Created new team: {}", team_name);
@@ -33,37 +44,94 @@ impl TeamManager {
         }
     }
 
+    pub fn delete_team(&mut self, storage: &mut dyn Storage, team_name: &str) -> bool {
+        if !self.teams.contains_key(team_name) {
+            println!("⚠️ Team {} not found!", team_name);
+            return false;
+        }
+        match storage.delete_team(team_name) {
+            Ok(_) => {
+                self.teams.remove(team_name);
+                println!("🗑️ Deleted team: {}", team_name);
+                true
+            }
+            Err(e) => {
+                println!("⚠️ Failed to delete team {}: {}", team_name, e);
+                false
+            }
+        }
+    }
+
+    /// Adds a container to the end of the team, or to the front when `lead` is set.
     pub fn add_to_team(
         &mut self,
+        storage: &mut dyn Storage,
         team_name: &str,
-        container_name: &str,
-        containers: &HashMap<String, Container>,
+        container_id: &str,
+        lead: bool,
     ) -> bool {
-        if let Some(team) = self.teams.get_mut(team_name) {
-            if containers.contains_key(container_name) {
-                team.push(container_name.to_string());
-                println!("➕ Added {} to team {}", container_name, team_name);
-                true
-            } else {
-                println!("⚠️ Pokémon {} not found!", container_name);
-                false
+        let Some(team) = self.teams.get(team_name) else {
+            println!("⚠️ Team {} not found!", team_name);
+            return false;
+        };
+        if team.iter().any(|id| id == container_id) {
+            println!(
+                "⚠️ Pokémon {} is already in team {}!",
+                container_id, team_name
+            );
+            return false;
+        }
+        if team.len() >= MAX_TEAM_SIZE {
+            println!(
+                "⚠️ Team {} already has {} members!",
+                team_name, MAX_TEAM_SIZE
+            );
+            return false;
+        }
+        let container = match storage.load_pokemon(container_id) {
+            Ok(Some(container)) => container,
+            _ => {
+                println!("⚠️ Pokémon {} not found!", container_id);
+                return false;
             }
+        };
+
+        let mut members = team.clone();
+        if lead {
+            members.insert(0, container_id.to_string());
         } else {
-            println!("⚠️ Team {} not found!", team_name);
-            false
+            members.push(container_id.to_string());
         }
+        if let Err(e) = storage.save_team(team_name, &members) {
+            println!("⚠️ Failed to update team {}: {}", team_name, e);
+            return false;
+        }
+        self.teams.insert(team_name.to_string(), members);
+        println!("➕ Added {} to team {}", container.name, team_name);
+        true
     }
 
-    pub fn remove_from_team(&mut self, team_name: &str, container_name: &str) -> bool {
-        if let Some(team) = self.teams.get_mut(team_name) {
-            if let Some(index) = team.iter().position(|name| name == container_name) {
-                team.remove(index);
-                println!("➖ Removed {} from team {}", container_name, team_name);
+    pub fn remove_from_team(
+        &mut self,
+        storage: &mut dyn Storage,
+        team_name: &str,
+        container_id: &str,
+    ) -> bool {
+        if let Some(team) = self.teams.get(team_name) {
+            if let Some(index) = team.iter().position(|id| id == container_id) {
+                let mut members = team.clone();
+                members.remove(index);
+                if let Err(e) = storage.save_team(team_name, &members) {
+                    println!("⚠️ Failed to update team {}: {}", team_name, e);
+                    return false;
+                }
+                self.teams.insert(team_name.to_string(), members);
+                println!("➖ Removed {} from team {}", container_id, team_name);
                 true
             } else {
                 println!(
                     "⚠️ Pokémon {} not found in team {}!",
-                    container_name, team_name
+                    container_id, team_name
                 );
                 false
             }
@@ -73,25 +141,53 @@ impl TeamManager {
         }
     }
 
-    pub fn team_info(&self, team_name: &str, containers: &HashMap<String, Container>) {
+    pub fn list_teams(&self) {
+        if self.teams.is_empty() {
+            println!("⚠️ No teams yet! Create one with `team create <name>`");
+            return;
+        }
+        for (team_name, members) in &self.teams {
+            println!(
+                "🌟 {} ({}/{} members)",
+                team_name,
+                members.len(),
+                MAX_TEAM_SIZE
+            );
+        }
+    }
+
+    pub fn team_info(&self, team_name: &str, storage: &dyn Storage) {
         if let Some(team) = self.teams.get(team_name) {
             println!("🌟 Team {}:", team_name);
-            for container_name in team {
-                if let Some(container) = containers.get(container_name) {
+            let mut containers = Vec::new();
+            for (position, container_id) in team.iter().enumerate() {
+                if let Ok(Some(container)) = storage.load_pokemon(container_id) {
                     println!(
-                        "Pokémon: {} | Level: {} | HP: {} | Status: {:?}",
-                        container.name, container.level, container.hp, container.stats
+                        "{} {} | ID: {} | Level: {} | HP: {}/{} | Type: {}",
+                        if position == 0 { "👑" } else { "  " },
+                        container.name,
+                        container.id,
+                        container.level,
+                        container.hp,
+                        container.max_hp,
+                        container.type_name()
                     );
+                    containers.push(container);
                 } else {
-                    println!("⚠️ Pokémon {} not found!", container_name);
+                    println!("⚠️ Pokémon {} not found!", container_id);
                 }
             }
+            let stats = Self::calculate_team_stats(&containers);
+            println!(
+                "Battles: {} won / {} lost | Total EXP: {} | Average level: {:.1}",
+                stats.battles_won, stats.battles_lost, stats.total_exp, stats.average_level
+            );
         } else {
             println!("⚠️ Team {} not found!", team_name);
         }
     }
 
-    pub fn calculate_team_stats(&self, containers: &HashMap<String, Container>) -> TeamStats {
+    pub fn calculate_team_stats(containers: &[Container]) -> TeamStats {
         let mut stats = TeamStats {
             battles_won: 0,
             battles_lost: 0,
@@ -99,23 +195,67 @@ impl TeamManager {
             average_level: 0.0,
         };
 
-        for (_, team) in &self.teams {
-            let mut total_level = 0;
-            for container_name in team {
-                if let Some(container) = containers.get(container_name) {
-                    stats.total_exp += container.exp;
-                    total_level += container.level;
-                }
-            }
-            if !team.is_empty() {
-                stats.average_level = total_level as f32 / team.len() as f32;
-            }
+        let mut total_level = 0;
+        for container in containers {
+            stats.battles_won += container.stats.battles_won;
+            stats.battles_lost += container.stats.battles_lost;
+            stats.total_exp += container.exp;
+            total_level += container.level;
+        }
+        if !containers.is_empty() {
+            stats.average_level = total_level as f32 / containers.len() as f32;
         }
         stats
     }
 }
 
-#[allow(dead_code)]
-impl TeamStats {
-    // ... existing code ...
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::PokemonType;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn test_team_rules_and_cleanup() {
+        let mut storage = MemoryStorage::new();
+        storage.create_namespace("kanto").unwrap();
+        let mut ids = Vec::new();
+        for i in 0..7 {
+            let mut container = Container::new(
+                &format!("Mon{}", i),
+                "kanto",
+                5,
+                20,
+                10,
+                10,
+                10,
+                PokemonType::Normal,
+            );
+            ids.push(storage.save_pokemon(&mut container).unwrap());
+        }
+
+        let mut teams = TeamManager::load(&storage).unwrap();
+        assert!(teams.create_team(&mut storage, "red"));
+        assert!(!teams.create_team(&mut storage, "red"));
+        for id in &ids[..6] {
+            assert!(teams.add_to_team(&mut storage, "red", id, false));
+        }
+        assert!(!teams.add_to_team(&mut storage, "red", &ids[6], false));
+        assert!(teams.remove_from_team(&mut storage, "red", &ids[5]));
+        assert!(!teams.add_to_team(&mut storage, "red", &ids[0], false));
+        assert!(teams.add_to_team(&mut storage, "red", &ids[6], true));
+
+        storage.delete_pokemon(&ids[1]).unwrap();
+        let teams = TeamManager::load(&storage).unwrap();
+        assert_eq!(
+            teams.teams["red"],
+            vec![
+                ids[6].clone(),
+                ids[0].clone(),
+                ids[2].clone(),
+                ids[3].clone(),
+                ids[4].clone()
+            ]
+        );
+    }
 }